@@ -18,7 +18,7 @@ fn run() {
     for result in reader.deserialize() {
         // We must tell Serde what type we want to deserialize into.
         let input: transaction::Input = result.unwrap();
-        executor.process(input);
+        let _ = executor.process(input);
     }
 
     // for i in executor.output() {
@@ -0,0 +1,331 @@
+//! command line handling shared by the drivers
+
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+pub const USAGE: &str =
+    "usage: [<input file> | -] [--rejections <path>] [--state <path>] [--wal <path>] [--checkpoint-every <rows>] [--lenient] [--dispute-policy <legacy|deposits-only|withdrawal-to-held>] [--negative-balance <allow|reject|partial-hold>] [--max-disputes <n>] [--dispute-window <rows> | --dispute-window-secs <seconds>] [--exposure <path>] [--audit <path>] [--tx-history <path>] [--journal <path>] [--check-invariants] [--queue-capacity <n>] [--batch-size <n>] [--parsers <n>]";
+
+///built-in dispute policies by command line name
+#[derive(Debug, Default, Clone, Copy)]
+pub enum DisputePolicy {
+    #[default]
+    Legacy,
+    DepositsOnly,
+    WithdrawalToHeld,
+}
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub input: Option<PathBuf>,        //None reads stdin
+    pub rejections: Option<PathBuf>,   //csv report of rejected inputs
+    pub state: Option<PathBuf>,        //executor snapshot loaded before and saved after processing
+    pub wal: Option<PathBuf>,          //write-ahead log replayed on start, single core driver only
+    pub checkpoint_every: Option<u64>, //rows between saves of the state, single core driver only
+    pub lenient: bool,                 //skip malformed rows instead of failing
+    pub dispute_policy: DisputePolicy,
+    pub negative_balance: transaction::NegativeBalancePolicy,
+    pub dispute_limits: transaction::DisputeLimits,
+    pub exposure: Option<PathBuf>,   //csv report of clients owing funds
+    pub audit: Option<PathBuf>,      //csv report of account lock changes
+    pub tx_history: Option<PathBuf>, //csv report of dispute status changes, kept in the state too
+    pub journal: Option<PathBuf>,    //csv report of journal postings, checked against the balances
+    pub check_invariants: bool,      //check balances after every input and fail if any broke
+    pub queue_capacity: Option<usize>, //batches queued per worker, threaded driver only
+    pub batch_size: Option<usize>,   //inputs per batch sent to a worker, threaded driver only
+    pub parsers: Option<usize>,      //csv parser threads, threaded driver only
+}
+
+impl Args {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Args, Box<dyn Error>> {
+        let mut parsed = Args::default();
+        let mut input = None;
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rejections" => {
+                    let path = args.next().ok_or("--rejections needs a path")?;
+                    parsed.rejections = Some(path.into());
+                }
+                "--state" => {
+                    let path = args.next().ok_or("--state needs a path")?;
+                    parsed.state = Some(path.into());
+                }
+                "--wal" => {
+                    let path = args.next().ok_or("--wal needs a path")?;
+                    parsed.wal = Some(path.into());
+                }
+                "--checkpoint-every" => {
+                    let n = args.next().ok_or("--checkpoint-every needs a number")?;
+                    parsed.checkpoint_every = Some(n.parse()?);
+                }
+                "--lenient" => parsed.lenient = true,
+                "--check-invariants" => parsed.check_invariants = true,
+                "--negative-balance" => {
+                    let name = args.next().ok_or("--negative-balance needs a name")?;
+                    parsed.negative_balance = match name.as_str() {
+                        "allow" => transaction::NegativeBalancePolicy::Allow,
+                        "reject" => transaction::NegativeBalancePolicy::Reject,
+                        "partial-hold" => transaction::NegativeBalancePolicy::PartialHold,
+                        _ => return Err(format!("unknown negative balance policy {}", name).into()),
+                    };
+                }
+                "--max-disputes" => {
+                    let n = args.next().ok_or("--max-disputes needs a number")?;
+                    parsed.dispute_limits.max_disputes = Some(n.parse()?);
+                }
+                "--dispute-window" | "--dispute-window-secs" => {
+                    let n = args.next().ok_or(format!("{} needs a number", arg))?;
+                    if parsed.dispute_limits.window.is_some() {
+                        return Err("only one dispute window can be set".into());
+                    }
+                    parsed.dispute_limits.window = Some(match arg.as_str() {
+                        "--dispute-window" => transaction::DisputeWindow::Inputs(n.parse()?),
+                        _ => transaction::DisputeWindow::Seconds(n.parse()?),
+                    });
+                }
+                "--audit" => {
+                    let path = args.next().ok_or("--audit needs a path")?;
+                    parsed.audit = Some(path.into());
+                }
+                "--tx-history" => {
+                    let path = args.next().ok_or("--tx-history needs a path")?;
+                    parsed.tx_history = Some(path.into());
+                }
+                "--journal" => {
+                    let path = args.next().ok_or("--journal needs a path")?;
+                    parsed.journal = Some(path.into());
+                }
+                "--exposure" => {
+                    let path = args.next().ok_or("--exposure needs a path")?;
+                    parsed.exposure = Some(path.into());
+                }
+                "--dispute-policy" => {
+                    let name = args.next().ok_or("--dispute-policy needs a name")?;
+                    parsed.dispute_policy = match name.as_str() {
+                        "legacy" => DisputePolicy::Legacy,
+                        "deposits-only" => DisputePolicy::DepositsOnly,
+                        "withdrawal-to-held" => DisputePolicy::WithdrawalToHeld,
+                        _ => return Err(format!("unknown dispute policy {}", name).into()),
+                    };
+                }
+                "--queue-capacity" => {
+                    let n = args.next().ok_or("--queue-capacity needs a number")?;
+                    parsed.queue_capacity = Some(n.parse()?);
+                }
+                "--batch-size" => {
+                    let n = args.next().ok_or("--batch-size needs a number")?;
+                    parsed.batch_size = Some(n.parse()?);
+                }
+                "--parsers" => {
+                    let n = args.next().ok_or("--parsers needs a number")?;
+                    parsed.parsers = Some(n.parse()?);
+                }
+                _ if arg.starts_with("--") => {
+                    return Err(format!("unknown option {}", arg).into());
+                }
+                _ if input.is_none() => input = Some(arg),
+                _ => return Err(format!("unexpected argument {}", arg).into()),
+            }
+        }
+        parsed.input = input.filter(|x| x != "-").map(PathBuf::from);
+        Ok(parsed)
+    }
+
+    pub fn open_input(&self) -> io::Result<Box<dyn io::Read + Send>> {
+        match &self.input {
+            Some(path) => transaction::open_input(path),
+            None => transaction::decompressed(io::stdin()),
+        }
+    }
+
+    ///open the rejections report if one was asked for
+    ///
+    ///a resumed run keeps what was reported for rows before line `resumed_at`
+    ///and appends to it, later rows are reported again as they are processed again
+    pub fn open_rejections(
+        &self,
+        resumed_at: Option<u64>,
+    ) -> Result<Option<csv::Writer<File>>, Box<dyn Error>> {
+        let Some(path) = &self.rejections else {
+            return Ok(None);
+        };
+        let resumed_at = match resumed_at {
+            Some(x) if path.exists() => x,
+            _ => return Ok(Some(csv::Writer::from_path(path)?)),
+        };
+        let mut rdr = csv::Reader::from_path(path)?;
+        rdr.headers()?;
+        let mut keep = rdr.position().byte();
+        let mut record = csv::StringRecord::new();
+        while rdr.read_record(&mut record)? {
+            let row: u64 = record.get(0).unwrap_or_default().parse()?;
+            if row >= resumed_at {
+                break;
+            }
+            keep = rdr.position().byte();
+        }
+        let file = OpenOptions::new().append(true).open(path)?;
+        file.set_len(keep)?;
+        //the header is only written to a report that has none yet
+        Ok(Some(
+            csv::WriterBuilder::new()
+                .has_headers(keep == 0)
+                .from_writer(file),
+        ))
+    }
+
+    ///executor restored from the state file, fresh if there is none yet
+    pub fn load_state(&self) -> Result<transaction::Executor, Box<dyn Error>> {
+        let executor = match &self.state {
+            Some(path) if path.exists() => {
+                let r = io::BufReader::new(File::open(path)?);
+                transaction::Executor::restore(r)?
+            }
+            _ => transaction::Executor::default(),
+        };
+        let mut executor = executor
+            .with_negative_balance_policy(self.negative_balance)
+            .with_dispute_limits(self.dispute_limits);
+        if self.tx_history.is_some() {
+            executor = executor.with_tx_history();
+        }
+        if self.journal.is_some() {
+            executor = executor.with_journal();
+        }
+        if self.check_invariants {
+            executor = executor.with_invariant_checks();
+        }
+        Ok(match self.dispute_policy {
+            DisputePolicy::Legacy => executor.with_dispute_policy(transaction::LegacyDisputes),
+            DisputePolicy::DepositsOnly => executor.with_dispute_policy(transaction::DepositsOnly),
+            DisputePolicy::WithdrawalToHeld => {
+                executor.with_dispute_policy(transaction::WithdrawalToHeld)
+            }
+        })
+    }
+
+    ///write the state file, replacing the old one only once the new one is complete
+    pub fn save_state(&self, executor: &transaction::Executor) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.state {
+            let mut tmp = path.clone().into_os_string();
+            tmp.push(".tmp");
+            let w = io::BufWriter::new(File::create(&tmp)?);
+            executor.snapshot(w)?;
+            fs::rename(&tmp, path)?;
+        }
+        Ok(())
+    }
+
+    ///write the exposure report if one was asked for
+    pub fn write_exposure(&self, executor: &transaction::Executor) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.exposure {
+            let mut w = csv::Writer::from_path(path)?;
+            for i in executor.exposures() {
+                w.serialize(i)?;
+            }
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    ///write the audit trail of account locks if asked for
+    pub fn write_audit(&self, executor: &transaction::Executor) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.audit {
+            let mut w = csv::Writer::from_path(path)?;
+            for i in executor.audit() {
+                w.serialize(i)?;
+            }
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    ///write the dispute status history of every tx if asked for
+    pub fn write_tx_history(&self, executor: &transaction::Executor) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.tx_history {
+            let mut w = csv::Writer::from_path(path)?;
+            for i in executor.tx_histories() {
+                w.serialize(i)?;
+            }
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    ///check the journal against the balances and write its postings if asked for
+    pub fn write_journal(&self, executor: &transaction::Executor) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.journal {
+            executor.check_journal()?;
+            let mut w = csv::Writer::from_path(path)?;
+            for i in executor.journal().unwrap_or_default() {
+                for line in i.lines() {
+                    w.serialize(line)?;
+                }
+            }
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    ///report inputs that broke an invariant, failing if there are any
+    pub fn report_violations(
+        &self,
+        executor: &transaction::Executor,
+    ) -> Result<(), Box<dyn Error>> {
+        let violations = executor.violations();
+        for i in violations {
+            eprintln!("violation {}", i);
+        }
+        if !violations.is_empty() {
+            return Err(format!("{} invariant violations", violations.len()).into());
+        }
+        Ok(())
+    }
+
+    pub fn ingest_mode(&self) -> transaction::IngestMode {
+        if self.lenient {
+            transaction::IngestMode::Lenient
+        } else {
+            transaction::IngestMode::Strict
+        }
+    }
+}
+
+///client balances without a currency, as written when no balance is of a named currency
+#[derive(Serialize)]
+struct PlainOutput {
+    client: transaction::Client,
+    available: transaction::Amount,
+    held: transaction::Amount,
+    total: transaction::Amount,
+    locked: bool,
+}
+
+///write client balances as csv, with a currency column only if a balance is of a named currency
+pub fn write_output<W: io::Write>(
+    w: W,
+    outputs: Vec<transaction::Output>,
+) -> Result<(), Box<dyn Error>> {
+    let named = outputs.iter().any(|x| x.currency.is_some());
+    let mut writer = csv::Writer::from_writer(w);
+    for i in outputs {
+        if named {
+            writer.serialize(i)?;
+        } else {
+            writer.serialize(PlainOutput {
+                client: i.client,
+                available: i.available,
+                held: i.held,
+                total: i.total,
+                locked: i.locked,
+            })?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -3,40 +3,175 @@
 //! execute following before running bench:
 //!  cargo run --release --bin generate_data
 
+mod common;
+
+use std::collections::HashMap;
 use std::env;
 use std::error::Error;
+use std::fs::File;
 use std::io;
-use std::path::Path;
 use std::process;
 
-fn run(path: &Path) -> Result<(), Box<dyn Error>> {
-    let mut reader = csv::Reader::from_path(path)?;
+fn run(args: &common::Args) -> Result<(), Box<dyn Error>> {
+    if let Some(n) = args.checkpoint_every {
+        if n == 0 {
+            return Err("--checkpoint-every must be at least 1".into());
+        }
+        if args.state.is_none() {
+            return Err("--checkpoint-every needs --state".into());
+        }
+    }
+
+    let mut executor = args.load_state()?;
+    let resume_at = executor.checkpoint();
+    let mut last_logged = None;
+    //recover inputs logged after the snapshot, then keep logging
+    let mut wal = match &args.wal {
+        Some(path) => {
+            if path.exists() {
+                let replayed = executor.replay(io::BufReader::new(File::open(path)?))?;
+                eprintln!("recovered {} logged inputs", replayed.applied);
+                if let Some(row) = replayed.last_position {
+                    eprintln!("last logged input was row {}", row);
+                }
+                //a finished run logged its inputs from another source, or ones the
+                //state may already reflect
+                if replayed.finished {
+                    eprintln!("the logged inputs are of a finished run");
+                } else {
+                    last_logged = replayed.last_position;
+                }
+                if replayed.torn {
+                    eprintln!("dropped a torn entry at the end of the log");
+                }
+            }
+            Some(transaction::Wal::open(path, executor.wal_seq())?)
+        }
+        None => None,
+    };
+
+    //an interrupted run is picked up after the rows its state already reflects
+    let mut reader = match (resume_at, &args.input) {
+        (Some(checkpoint), Some(path)) => {
+            transaction::InputReader::resume(path, args.ingest_mode(), checkpoint)?
+        }
+        (Some(checkpoint), None) => {
+            let mut reader =
+                transaction::InputReader::from_reader(args.open_input()?, args.ingest_mode())?;
+            reader.skip_to(checkpoint.line)?;
+            reader
+        }
+        (None, _) => transaction::InputReader::from_reader(args.open_input()?, args.ingest_mode())?,
+    };
+    //rows an interrupted run logged after the state was saved have been replayed
+    //already, whether or not the state has a checkpoint
+    if let Some(row) = last_logged {
+        reader.skip_to(row + 1)?;
+    }
+    if let Some(checkpoint) = resume_at {
+        eprintln!(
+            "resuming at line {} after checkpoint at line {}",
+            reader.checkpoint().line,
+            checkpoint.line
+        );
+    } else if last_logged.is_some() {
+        eprintln!(
+            "resuming at line {} after the logged inputs",
+            reader.checkpoint().line
+        );
+    }
+    let resumed_at =
+        (resume_at.is_some() || last_logged.is_some()).then(|| reader.checkpoint().line);
+    let mut rejections = args.open_rejections(resumed_at)?;
 
-    let mut executor = transaction::Executor::default();
-    for result in reader.deserialize() {
-        // We must tell Serde what type we want to deserialize into.
-        let input: transaction::Input = result?;
-        executor.process(input);
+    let mut rows = 0;
+    let mut rejected: HashMap<transaction::Rejection, u64> = HashMap::new();
+    loop {
+        let next = reader.next();
+        //rows rejected for their amount come before the row read
+        for x in reader.take_rejected() {
+            *rejected.entry(x.reason).or_default() += 1;
+            if let Some(w) = rejections.as_mut() {
+                w.serialize(x)?;
+            }
+        }
+        let Some(result) = next else { break };
+        let (row, input) = result?;
+        let res = match wal.as_mut() {
+            Some(w) => executor.process_logged(row, input, w)?,
+            None => executor.process_at(row, input),
+        };
+        if let Err(reason) = res {
+            *rejected.entry(reason).or_default() += 1;
+            if let Some(w) = rejections.as_mut() {
+                w.serialize(transaction::RejectedInput::from((row, input, reason)))?;
+            }
+        }
+        rows += 1;
+        if args.checkpoint_every.is_some_and(|n| rows % n == 0) {
+            //what was reported up to the checkpoint is kept if the run is resumed
+            if let Some(w) = rejections.as_mut() {
+                w.flush()?;
+            }
+            executor.set_checkpoint(Some(reader.checkpoint()));
+            save(args, &executor, wal.as_mut())?;
+        }
     }
+    executor.set_checkpoint(None);
 
-    let mut writer = csv::Writer::from_writer(io::stdout());
+    common::write_output(io::stdout(), executor.output().collect())?;
+    args.write_exposure(&executor)?;
+    args.write_audit(&executor)?;
+    args.write_tx_history(&executor)?;
+    args.write_journal(&executor)?;
+    args.report_violations(&executor)?;
+    if let Some(w) = wal.as_mut() {
+        w.finish_run()?;
+    }
+    save(args, &executor, wal.as_mut())?;
 
-    for i in executor.output() {
-        writer.serialize(i)?;
+    if let Some(mut w) = rejections {
+        w.flush()?;
+    }
+    for i in reader.skipped() {
+        eprintln!("skipped {}", i);
+    }
+    for (reason, count) in rejected {
+        eprintln!("rejected ({}): {}", reason, count);
     }
 
-    writer.flush()?;
+    Ok(())
+}
 
+///save the state, the log is synced first and emptied once the snapshot covers it
+fn save(
+    args: &common::Args,
+    executor: &transaction::Executor,
+    wal: Option<&mut transaction::Wal<io::BufWriter<File>>>,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(w) = wal {
+        w.sync()?;
+        args.save_state(executor)?;
+        //without a snapshot the log is the only record
+        if args.state.is_some() {
+            w.reset()?;
+        }
+    } else {
+        args.save_state(executor)?;
+    }
     Ok(())
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    assert_eq!(args.len(), 2, "please provide input file path");
-    let file = &args[1];
-    let path = Path::new(&file);
-    if let Err(err) = run(path) {
-        println!("failure: {:?}", err);
+    let args = match common::Args::parse(env::args()) {
+        Ok(x) => x,
+        Err(err) => {
+            eprintln!("{}\n{}", err, common::USAGE);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = run(&args) {
+        println!("failure: {}", err);
         process::exit(1);
     }
 }
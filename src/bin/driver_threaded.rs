@@ -3,109 +3,100 @@
 //! execute following before running bench:
 //!  cargo run --release --bin generate_data
 
-extern crate crossbeam;
-extern crate num_cpus;
+mod common;
+
 extern crate transaction;
 
-use crossbeam::channel::unbounded;
-use crossbeam::thread;
+use std::collections::HashMap;
 use std::env;
 use std::error::Error;
 use std::io;
-use std::path::Path;
 use std::process;
 
-pub enum Msg {
-    Item(transaction::Input),
-    End,
-}
-
-fn run(path: &Path) -> Result<(), Box<dyn Error>> {
-    let mut reader = csv::Reader::from_path(path)?;
-
-    // let num_workers: usize = 3;
-    let num_workers: usize = num_cpus::get();
-    // println!("number of cores: {}", num_workers);
-
-    //concurrent msg channels for workers
-    let mut channels_sender = vec![];
-    let mut channels_receiver = vec![];
-
-    let mut executors = vec![];
-
-    for _ in 0..num_workers {
-        let (sender, receiver) = unbounded();
-        channels_sender.push(sender);
-        channels_receiver.push(receiver);
-        executors.push(transaction::Executor::default());
+fn run(args: &common::Args) -> Result<(), Box<dyn Error>> {
+    // let executor = transaction::ParallelExecutor::new(3);
+    let mut executor = transaction::ParallelExecutor::default();
+    if let Some(capacity) = args.queue_capacity {
+        executor = executor.with_queue_capacity(capacity);
     }
-
-    let executors_finished: Vec<_> = thread::scope(|s| {
-        let handle_reader = s.spawn(|_| {
-            for result in reader.deserialize() {
-                // We must tell Serde what type we want to deserialize into.
-                let input: transaction::Input = result.expect("failed to get input");
-                //client must be mapped to a same worker in order for result to be correct
-                let sender = &channels_sender[input.client.0 as usize % num_workers];
-                sender.send(Msg::Item(input)).unwrap();
-            }
-            for i in &channels_sender {
-                i.send(Msg::End).unwrap();
-            }
-        });
-
-        let mut handles_executors = vec![];
-
-        for (idx, executor) in executors.iter_mut().enumerate() {
-            let receiver = channels_receiver[idx].clone();
-            let h_executor = s.spawn(move |_| {
-                loop {
-                    match receiver.recv() {
-                        Ok(x) => match x {
-                            Msg::Item(item) => {
-                                executor.process(item);
-                            }
-                            _ => {
-                                break;
-                            }
-                        },
-                        _ => {
-                            panic!("receiver failure");
-                        }
-                    }
-                }
-                executor
-            });
-            handles_executors.push(h_executor);
+    if let Some(batch_size) = args.batch_size {
+        if batch_size == 0 {
+            return Err("--batch-size must be at least 1".into());
         }
+        executor = executor.with_batch_size(batch_size);
+    }
+    if let Some(parsers) = args.parsers {
+        if parsers == 0 {
+            return Err("--parsers must be at least 1".into());
+        }
+        executor = executor.with_parser_threads(parsers);
+    }
+    // println!("number of cores: {}", executor.num_workers());
 
-        handle_reader.join().unwrap();
-
-        handles_executors
-            .into_iter()
-            .map(|x| x.join().unwrap())
-            .collect()
-    })
-    .unwrap();
-    //sync point
+    if args.wal.is_some() {
+        return Err("--wal is only supported by the single core driver".into());
+    }
+    if args.checkpoint_every.is_some() {
+        return Err("--checkpoint-every is only supported by the single core driver".into());
+    }
+    let state = args.load_state()?;
+    if state.checkpoint().is_some() {
+        return Err(
+            "state is from an interrupted run, resume it with the single core driver".into(),
+        );
+    }
+    //pipes and stdin can't be split between parser threads
+    let mut finished = match &args.input {
+        Some(path) if path.is_file() => executor.run_file_from(state, path, args.ingest_mode())?,
+        _ => executor.run_reader_from(state, args.open_input()?, args.ingest_mode())?,
+    };
 
     //now write result from executors
-    let mut writer = csv::Writer::from_writer(io::stdout());
-    for i in executors_finished.into_iter().flat_map(|x| x.output()) {
-        writer.serialize(i)?;
+    common::write_output(io::stdout(), std::mem::take(&mut finished.outputs))?;
+
+    if let Some(mut w) = args.open_rejections(None)? {
+        for i in &finished.rejections {
+            w.serialize(i)?;
+        }
+        w.flush()?;
+    }
+    let mut rejected: HashMap<transaction::Rejection, u64> = HashMap::new();
+    for i in &finished.rejections {
+        *rejected.entry(i.reason).or_default() += 1;
+    }
+    for i in &finished.skipped {
+        eprintln!("skipped {}", i);
     }
-    writer.flush()?;
+    for (reason, count) in rejected {
+        eprintln!("rejected ({}): {}", reason, count);
+    }
+    for (idx, i) in finished.stats.iter().enumerate() {
+        eprintln!(
+            "shard {}: {} inputs in {} batches, parsers stalled {} times for {:?}",
+            idx, i.inputs, i.batches, i.stalls, i.stalled
+        );
+    }
+    let state = finished.into_executor();
+    args.write_exposure(&state)?;
+    args.write_audit(&state)?;
+    args.write_tx_history(&state)?;
+    args.write_journal(&state)?;
+    args.report_violations(&state)?;
+    args.save_state(&state)?;
 
     Ok(())
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    assert_eq!(args.len(), 2, "please provide input file path");
-    let file = &args[1];
-    let path = Path::new(&file);
-    if let Err(err) = run(path) {
-        println!("failure: {:?}", err);
+    let args = match common::Args::parse(env::args()) {
+        Ok(x) => x,
+        Err(err) => {
+            eprintln!("{}\n{}", err, common::USAGE);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = run(&args) {
+        println!("failure: {}", err);
         process::exit(1);
     }
 }
@@ -1,12 +1,15 @@
 //! used to generate test data
+//!
+//!  cargo run --release --bin generate_data [output path]
+//!
+//! output ending in .gz or .zst is compressed
 
 use rand::seq::SliceRandom;
 use rand::Rng;
 use std::collections::HashSet;
+use std::env;
 use std::error::Error;
-use std::ffi::OsString;
 use std::ops::Range;
-use std::path::Path;
 use std::process;
 
 extern crate transaction;
@@ -76,20 +79,23 @@ impl InputBuilder {
             client: transaction::Client(client),
             tx,
             amount: amnt, //don't care for resolve or chargeback
+            timestamp: None,
+            currency: None,
+            to: None,
         }
     }
 }
 
 fn run() -> Result<(), Box<dyn Error>> {
     //create a write to a file
-    let path: OsString = Path::new("./sample_input.txt").into();
-    let mut wtr = csv::Writer::from_path(path)?;
+    let path = env::args().nth(1).unwrap_or("./sample_input.txt".into());
+    let mut wtr = csv::Writer::from_writer(transaction::create_output(path)?);
 
     //bound this for testing
     let mut input_builder = InputBuilder::new(
         transaction::Client(0)..transaction::Client(1000),
         transaction::Tx(0)..transaction::Tx(u32::MAX),
-        transaction::Amount(-999.)..transaction::Amount(999.),
+        transaction::Amount(1)..transaction::Amount(999_0000),
     );
 
     let num_inputs = 10_000_000;
@@ -98,7 +104,8 @@ fn run() -> Result<(), Box<dyn Error>> {
         let input = input_builder.sample_random();
         wtr.serialize(input)?;
     }
-    wtr.flush()?;
+    //a compressed stream is only complete once finished
+    wtr.into_inner()?.finish()?;
     Ok(())
 }
 
@@ -0,0 +1,134 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+///compression format of an input or output stream
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    ///format implied by a .gz or .zst extension
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    ///format implied by the leading bytes of a stream
+    pub fn from_magic(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if bytes.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else {
+            Compression::None
+        }
+    }
+
+    ///format of a file, by extension and failing that by magic bytes
+    pub fn of_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        if let Some(x) = Self::from_extension(&path) {
+            return Ok(x);
+        }
+        let mut magic = vec![];
+        File::open(path)?
+            .take(ZSTD_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        Ok(Self::from_magic(&magic))
+    }
+
+    ///stream decompressing rdr
+    pub fn decoder<R: Read + Send + 'static>(self, rdr: R) -> io::Result<Box<dyn Read + Send>> {
+        Ok(match self {
+            Compression::None => Box::new(rdr),
+            //concatenated members are common in gzip drops
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(rdr)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(rdr)?),
+        })
+    }
+
+    ///stream compressing into wtr, to be finished with `Encoder::finish`
+    pub fn encoder<W: Write>(self, wtr: W) -> io::Result<Encoder<W>> {
+        Ok(match self {
+            Compression::None => Encoder::None(wtr),
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                wtr,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(wtr, 0)?),
+        })
+    }
+}
+
+/// Stream compressing into a writer
+///
+/// The trailer of a compressed stream is only written by `finish`, which
+/// reports errors doing so. A dropped encoder may leave a truncated stream.
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    ///write what is left of the stream and flush it, returning the writer
+    pub fn finish(self) -> io::Result<W> {
+        let mut wtr = match self {
+            Encoder::None(x) => x,
+            Encoder::Gzip(x) => x.finish()?,
+            Encoder::Zstd(x) => x.finish()?,
+        };
+        wtr.flush()?;
+        Ok(wtr)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(x) => x.write(buf),
+            Encoder::Gzip(x) => x.write(buf),
+            Encoder::Zstd(x) => x.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(x) => x.flush(),
+            Encoder::Gzip(x) => x.flush(),
+            Encoder::Zstd(x) => x.flush(),
+        }
+    }
+}
+
+///open a file for reading, decompressing it if needed
+pub fn open_input<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read + Send>> {
+    let compression = Compression::of_file(&path)?;
+    compression.decoder(File::open(path)?)
+}
+
+///decompress a stream such as stdin if it starts with gzip or zstd magic bytes
+pub fn decompressed<R: Read + Send + 'static>(mut rdr: R) -> io::Result<Box<dyn Read + Send>> {
+    let mut magic = vec![];
+    (&mut rdr)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let compression = Compression::from_magic(&magic);
+    //put the peeked bytes back in front
+    compression.decoder(io::Cursor::new(magic).chain(rdr))
+}
+
+///create a file for writing, compressed as its extension says
+pub fn create_output<P: AsRef<Path>>(path: P) -> io::Result<Encoder<File>> {
+    let compression = Compression::from_extension(&path).unwrap_or(Compression::None);
+    compression.encoder(File::create(path)?)
+}
@@ -1,5 +1,8 @@
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
 use std::option::Option;
+use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct Client(pub u16);
@@ -7,28 +10,377 @@ pub struct Client(pub u16);
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct Tx(pub u32);
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
-pub struct Amount(pub f32);
+///fixed-point amount with 4 decimal places, stored scaled by 10^4 (eg: Amount(1_2345) is 1.2345)
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Default)]
+pub struct Amount(pub i64);
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+impl Amount {
+    pub const DECIMALS: usize = 4;
+    pub const SCALE: i64 = 10_000;
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn checked_neg(self) -> Option<Amount> {
+        self.0.checked_neg().map(Amount)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AmountParseError {
+    Empty,
+    Invalid,    //not a plain decimal number
+    NonFinite,  //nan or infinity
+    TooPrecise, //more than Amount::DECIMALS fractional digits
+    Overflow,
+}
+
+impl fmt::Display for AmountParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            AmountParseError::Empty => "amount is empty",
+            AmountParseError::Invalid => "amount is not a decimal number",
+            AmountParseError::NonFinite => "amount is not finite",
+            AmountParseError::TooPrecise => "amount has more than 4 decimal places",
+            AmountParseError::Overflow => "amount is out of range",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl Error for AmountParseError {}
+
+impl FromStr for Amount {
+    type Err = AmountParseError;
+
+    ///parse a decimal string such as "-1.2345" exactly, without going through a float
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(AmountParseError::Empty);
+        }
+        let (negative, unsigned) = match s.as_bytes()[0] {
+            b'-' => (true, &s[1..]),
+            b'+' => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (whole, frac) = match unsigned.split_once('.') {
+            Some((whole, frac)) => (whole, frac),
+            None => (unsigned, ""),
+        };
+        let digits = || whole.bytes().chain(frac.bytes());
+        if digits().next().is_none() || !digits().all(|b| b.is_ascii_digit()) {
+            return match unsigned.to_ascii_lowercase().as_str() {
+                "nan" | "inf" | "infinity" => Err(AmountParseError::NonFinite),
+                _ => Err(AmountParseError::Invalid),
+            };
+        }
+        if frac.len() > Amount::DECIMALS {
+            return Err(AmountParseError::TooPrecise);
+        }
+
+        let mut scaled: i64 = 0;
+        for b in digits().chain(std::iter::repeat_n(b'0', Amount::DECIMALS - frac.len())) {
+            scaled = scaled
+                .checked_mul(10)
+                .and_then(|x| x.checked_add(i64::from(b - b'0')))
+                .ok_or(AmountParseError::Overflow)?;
+        }
+        Ok(Amount(if negative { -scaled } else { scaled }))
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = Amount::SCALE as u64;
+        let abs = self.0.unsigned_abs();
+        let sign = if self.0 < 0 { "-" } else { "" };
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            abs / scale,
+            abs % scale,
+            width = Amount::DECIMALS
+        )
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AmountVisitor;
+
+        impl<'de> de::Visitor<'de> for AmountVisitor {
+            type Value = Amount;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a decimal amount with at most 4 decimal places")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Amount, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(AmountVisitor)
+    }
+}
+
+///currency code of up to 8 ascii letters or digits, kept in uppercase
+///
+///the default is the unnamed currency of inputs without one, written as an empty field
+#[derive(Debug, Default, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Currency([u8; 8]);
+
+impl Currency {
+    pub const MAX_LEN: usize = 8;
+
+    ///the code as stored, uppercase ascii padded with zeros, all zeros for the unnamed currency
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+
+    ///the code, empty for the unnamed currency
+    pub fn as_str(&self) -> &str {
+        let len = self.0.iter().position(|x| *x == 0).unwrap_or(Self::MAX_LEN);
+        //only ascii is ever stored
+        std::str::from_utf8(&self.0[..len]).unwrap()
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() > Self::MAX_LEN || !s.bytes().all(|b| b.is_ascii_alphanumeric())
+        {
+            return Err(format!("invalid currency {:?}", s));
+        }
+        let mut code = [0u8; Self::MAX_LEN];
+        for (x, b) in code.iter_mut().zip(s.bytes()) {
+            *x = b.to_ascii_uppercase();
+        }
+        Ok(Currency(code))
+    }
+}
+
+///a code as stored, uppercase ascii letters or digits padded with zeros
+impl TryFrom<[u8; 8]> for Currency {
+    type Error = [u8; 8];
+
+    fn try_from(code: [u8; 8]) -> Result<Self, Self::Error> {
+        let len = code.iter().position(|x| *x == 0).unwrap_or(Self::MAX_LEN);
+        let valid = len > 0
+            && code[..len]
+                .iter()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+            && code[len..].iter().all(|b| *b == 0);
+        if valid {
+            Ok(Currency(code))
+        } else {
+            Err(code)
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CurrencyVisitor;
+
+        impl<'de> de::Visitor<'de> for CurrencyVisitor {
+            type Value = Currency;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a currency code such as USD")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Currency, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(CurrencyVisitor)
+    }
+}
+
+///serialized in lowercase, parsed case-insensitively with both "withdrawal" and "withdrawl" accepted
+#[derive(Debug, Serialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum InputType {
     Deposit,
+    #[serde(rename = "withdrawal")]
     Withdrawl,
     Dispute,
     Resolve,
     Chargeback,
+    Lock,     //admin action, tx is the reference of the request
+    Unlock,   //admin action reinstating an account, tx is the reference of the request
+    Transfer, //moves amount from client to the client in the to column
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl FromStr for InputType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ty = match s.to_ascii_lowercase().as_str() {
+            "deposit" => InputType::Deposit,
+            "withdrawal" | "withdrawl" => InputType::Withdrawl,
+            "dispute" => InputType::Dispute,
+            "resolve" => InputType::Resolve,
+            "chargeback" => InputType::Chargeback,
+            "lock" => InputType::Lock,
+            "unlock" => InputType::Unlock,
+            "transfer" => InputType::Transfer,
+            _ => return Err(format!("unknown input type {:?}", s)),
+        };
+        Ok(ty)
+    }
+}
+
+impl<'de> Deserialize<'de> for InputType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct InputTypeVisitor;
+
+        impl<'de> de::Visitor<'de> for InputTypeVisitor {
+            type Value = InputType;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an input type such as deposit or withdrawal")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<InputType, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(InputTypeVisitor)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(try_from = "InputRow")]
 pub struct Input {
     #[serde(rename = "type")]
     pub ty: InputType,
     pub client: Client,
     pub tx: Tx,
     pub amount: Option<Amount>,
+    #[serde(default)]
+    pub timestamp: Option<u64>, //seconds, from an optional column
+    #[serde(default)]
+    pub currency: Option<Currency>, //from an optional column, the unnamed currency if None
+    #[serde(default)]
+    pub to: Option<Client>, //destination of a transfer, from an optional column
+}
+
+///input as read, amounts that are numbers but not valid amounts are kept to be rejected
+#[derive(Deserialize)]
+pub(crate) struct InputRow {
+    #[serde(rename = "type")]
+    ty: InputType,
+    client: Client,
+    tx: Tx,
+    amount: Option<CheckedAmount>,
+    #[serde(default)]
+    timestamp: Option<u64>,
+    #[serde(default)]
+    currency: Option<Currency>,
+    #[serde(default)]
+    to: Option<Client>,
+}
+
+impl InputRow {
+    ///the input, without its amount if that is not a valid one, with why and the amount as given
+    pub(crate) fn checked(self) -> (Input, Option<(InvalidInput, String)>) {
+        let (amount, invalid) = match self.amount.map(|x| x.0) {
+            Some(Ok(amount)) => (Some(amount), None),
+            Some(Err(e)) => (None, Some(e)),
+            None => (None, None),
+        };
+        let input = Input {
+            ty: self.ty,
+            client: self.client,
+            tx: self.tx,
+            amount,
+            timestamp: self.timestamp,
+            currency: self.currency,
+            to: self.to,
+        };
+        (input, invalid)
+    }
+}
+
+impl TryFrom<InputRow> for Input {
+    type Error = InvalidInput;
+
+    fn try_from(x: InputRow) -> Result<Self, Self::Error> {
+        match x.checked() {
+            (input, None) => Ok(input),
+            (_, Some((e, _))) => Err(e),
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+///amount column of a row, non-finite and over-precise numbers are invalid inputs
+///rather than malformed rows and keep their text
+struct CheckedAmount(Result<Amount, (InvalidInput, String)>);
+
+impl<'de> Deserialize<'de> for CheckedAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CheckedAmountVisitor;
+
+        impl<'de> de::Visitor<'de> for CheckedAmountVisitor {
+            type Value = CheckedAmount;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a decimal amount")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<CheckedAmount, E> {
+                match v.parse() {
+                    Ok(x) => Ok(CheckedAmount(Ok(x))),
+                    Err(AmountParseError::NonFinite) => Ok(CheckedAmount(Err((
+                        InvalidInput::NonFiniteAmount,
+                        v.to_string(),
+                    )))),
+                    Err(AmountParseError::TooPrecise) => Ok(CheckedAmount(Err((
+                        InvalidInput::TooPreciseAmount,
+                        v.to_string(),
+                    )))),
+                    Err(e) => Err(E::custom(e)),
+                }
+            }
+        }
+
+        deserializer.deserialize_str(CheckedAmountVisitor)
+    }
+}
+
+#[derive(Debug, Serialize, Copy, Clone, Eq, Hash, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum DisputeStatus {
     Eligible, //tx can be disputed, transition to pending
     Pending, //tx can be resolved (transition to eligible), or can be chargeback (transition to complete)
@@ -40,37 +392,94 @@ pub enum DisputeStatus {
 pub enum InputInternal {
     Deposit(Client, Tx, Amount, DisputeStatus),
     Withdrawl(Client, Tx, Amount, DisputeStatus),
-    Dispute(Client, Tx),
+    Dispute(Client, Tx, Option<Amount>), //portion of the tx disputed, all of it if None
     Resolve(Client, Tx),
     Chargeback(Client, Tx),
+    Lock(Client, Tx),
+    Unlock(Client, Tx),
+    Transfer(Client, Client, Tx, Amount), //from, to
 }
 
-impl From<Input> for InputInternal {
-    fn from(input: Input) -> Self {
-        match input.ty {
-            InputType::Deposit => Self::Deposit(
-                input.client,
-                input.tx,
-                input.amount.expect("amount not present"),
-                DisputeStatus::Eligible,
-            ),
-            InputType::Withdrawl => Self::Withdrawl(
-                input.client,
-                input.tx,
-                input.amount.expect("amount not present"),
-                DisputeStatus::Eligible,
-            ),
-            InputType::Dispute => Self::Dispute(input.client, input.tx),
+impl TryFrom<Input> for InputInternal {
+    type Error = InvalidInput;
+
+    ///validate the amount of an input: deposits, withdrawls and transfers need a positive one,
+    ///disputes may have one and others none. transfers also need another client to go to
+    fn try_from(input: Input) -> Result<Self, Self::Error> {
+        let amount = match (input.ty, input.amount) {
+            (InputType::Deposit | InputType::Withdrawl | InputType::Transfer, None) => {
+                return Err(InvalidInput::MissingAmount)
+            }
+            (InputType::Deposit | InputType::Withdrawl | InputType::Transfer, Some(x))
+                if x <= Amount::ZERO =>
+            {
+                return Err(InvalidInput::NonPositiveAmount)
+            }
+            (InputType::Dispute, Some(x)) if x <= Amount::ZERO => {
+                return Err(InvalidInput::NonPositiveAmount)
+            }
+            (
+                InputType::Resolve | InputType::Chargeback | InputType::Lock | InputType::Unlock,
+                Some(_),
+            ) => return Err(InvalidInput::UnexpectedAmount),
+            (_, x) => x.unwrap_or_default(),
+        };
+        Ok(match input.ty {
+            InputType::Deposit => {
+                Self::Deposit(input.client, input.tx, amount, DisputeStatus::Eligible)
+            }
+            InputType::Withdrawl => {
+                Self::Withdrawl(input.client, input.tx, amount, DisputeStatus::Eligible)
+            }
+            InputType::Dispute => Self::Dispute(input.client, input.tx, input.amount),
             InputType::Resolve => Self::Resolve(input.client, input.tx),
             InputType::Chargeback => Self::Chargeback(input.client, input.tx),
-        }
+            InputType::Lock => Self::Lock(input.client, input.tx),
+            InputType::Unlock => Self::Unlock(input.client, input.tx),
+            InputType::Transfer => match input.to {
+                None => return Err(InvalidInput::MissingDestination),
+                Some(to) if to == input.client => return Err(InvalidInput::SelfTransfer),
+                Some(to) => Self::Transfer(input.client, to, input.tx, amount),
+            },
+        })
     }
 }
 
-///output client data
+///input whose amount or destination doesn't fit its type
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InvalidInput {
+    MissingAmount,      //deposit, withdrawl or transfer without an amount
+    NonPositiveAmount,  //deposit, withdrawl, transfer or dispute of zero or less
+    NonFiniteAmount,    //amount of nan or infinity
+    TooPreciseAmount,   //amount with more than Amount::DECIMALS fractional digits
+    UnexpectedAmount,   //resolve, chargeback or admin action with an amount
+    MissingDestination, //transfer without a to client
+    SelfTransfer,       //transfer to the client it is from
+}
+
+impl fmt::Display for InvalidInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            InvalidInput::MissingAmount => "missing amount",
+            InvalidInput::NonPositiveAmount => "non-positive amount",
+            InvalidInput::NonFiniteAmount => "non-finite amount",
+            InvalidInput::TooPreciseAmount => "amount too precise",
+            InvalidInput::UnexpectedAmount => "unexpected amount",
+            InvalidInput::MissingDestination => "missing destination",
+            InvalidInput::SelfTransfer => "transfer to self",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl Error for InvalidInput {}
+
+///output client data, one per client and currency
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Output {
     pub client: Client,
+    #[serde(default)]
+    pub currency: Option<Currency>, //None for the unnamed currency
     pub available: Amount,
     pub held: Amount,
     pub total: Amount,
@@ -1,24 +1,350 @@
 use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use serde::Serialize;
 
 use crate::core::*;
+use crate::ingest::*;
+use crate::invariant::*;
+use crate::journal::*;
+use crate::policy::*;
+use crate::wal::*;
+
+///what an applied input did
+#[derive(Debug, Serialize, Clone, Copy, Hash, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Applied {
+    Deposit,
+    #[serde(rename = "withdrawal")]
+    Withdrawl,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Lock,
+    Unlock,
+    Transfer,
+}
+
+///why an input had no effect
+#[derive(Debug, Serialize, Clone, Copy, Hash, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rejection {
+    DuplicateTx, //deposit, withdrawl or transfer reuses a tx id already seen for the client
+    InsufficientFunds, //withdrawl or transfer larger than available
+    UnknownTx, //dispute, resolve or chargeback of a tx with no deposit or withdrawl record of the client
+    CurrencyMismatch, //dispute, resolve or chargeback names a different currency than the tx
+    MissingAmount, //deposit, withdrawl or transfer without an amount
+    NonPositiveAmount, //deposit, withdrawl, transfer or dispute of zero or less
+    NonFiniteAmount, //amount of nan or infinity
+    TooPreciseAmount, //amount with more than 4 decimal places
+    UnexpectedAmount, //resolve, chargeback or admin action with an amount
+    MissingDestination, //transfer without a to client
+    SelfTransfer, //transfer to the client it is from
+    AccountLocked, //client, or either client of a transfer, is locked
+    AlreadyDisputed, //dispute of a tx that is pending or charged back
+    NotDisputed, //resolve or chargeback of a tx that is not pending
+    NotDisputable, //dispute the dispute policy doesn't allow
+    DisputeTooLarge, //dispute of more than the tx amount
+    TooManyDisputes, //dispute of a tx disputed as often as the limit allows
+    WindowClosed, //dispute after the dispute window of the tx
+    AlreadyLocked, //lock of a locked account
+    NotLocked, //unlock of an account that is not locked
+    Overflow,  //balance arithmetic out of range of Amount
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Rejection::DuplicateTx => "duplicate tx",
+            Rejection::InsufficientFunds => "insufficient funds",
+            Rejection::UnknownTx => "unknown tx",
+            Rejection::CurrencyMismatch => "currency mismatch",
+            Rejection::MissingAmount => "missing amount",
+            Rejection::NonPositiveAmount => "non-positive amount",
+            Rejection::NonFiniteAmount => "non-finite amount",
+            Rejection::TooPreciseAmount => "amount too precise",
+            Rejection::UnexpectedAmount => "unexpected amount",
+            Rejection::MissingDestination => "missing destination",
+            Rejection::SelfTransfer => "transfer to self",
+            Rejection::AccountLocked => "account locked",
+            Rejection::AlreadyDisputed => "already disputed",
+            Rejection::NotDisputed => "not disputed",
+            Rejection::NotDisputable => "not disputable",
+            Rejection::DisputeTooLarge => "dispute larger than tx",
+            Rejection::TooManyDisputes => "too many disputes",
+            Rejection::WindowClosed => "dispute window closed",
+            Rejection::AlreadyLocked => "already locked",
+            Rejection::NotLocked => "not locked",
+            Rejection::Overflow => "amount overflow",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl Error for Rejection {}
+
+impl From<InvalidInput> for Rejection {
+    fn from(x: InvalidInput) -> Self {
+        match x {
+            InvalidInput::MissingAmount => Rejection::MissingAmount,
+            InvalidInput::NonPositiveAmount => Rejection::NonPositiveAmount,
+            InvalidInput::NonFiniteAmount => Rejection::NonFiniteAmount,
+            InvalidInput::TooPreciseAmount => Rejection::TooPreciseAmount,
+            InvalidInput::UnexpectedAmount => Rejection::UnexpectedAmount,
+            InvalidInput::MissingDestination => Rejection::MissingDestination,
+            InvalidInput::SelfTransfer => Rejection::SelfTransfer,
+        }
+    }
+}
+
+///rejected input with its original fields, row number in the source and reason
+#[derive(Debug, Serialize, Clone)]
+pub struct RejectedInput {
+    pub row: u64, //line number in the csv source, header is line 1
+    #[serde(rename = "type")]
+    pub ty: InputType,
+    pub client: Client,
+    pub tx: Tx,
+    pub amount: Option<String>, //as given if it is no valid amount
+    pub timestamp: Option<u64>,
+    pub currency: Option<Currency>,
+    pub to: Option<Client>, //empty unless given, so every row has the same columns
+    pub reason: Rejection,
+}
+
+impl RejectedInput {
+    ///row of an input whose amount is a number but not a valid amount
+    pub(crate) fn of_amount(row: u64, input: Input, reason: InvalidInput, amount: String) -> Self {
+        Self {
+            amount: Some(amount),
+            ..Self::from((row, input, reason.into()))
+        }
+    }
+}
+
+impl From<(u64, Input, Rejection)> for RejectedInput {
+    fn from((row, input, reason): (u64, Input, Rejection)) -> Self {
+        Self {
+            row,
+            ty: input.ty,
+            client: input.client,
+            tx: input.tx,
+            amount: input.amount.map(|x| x.to_string()),
+            timestamp: input.timestamp,
+            currency: input.currency,
+            to: input.to,
+            reason,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct ClientData {
     pub avai: Amount,
     pub held: Amount,
     pub total: Amount,
-    pub locked: bool,
+    pub flagged: bool, //a dispute held less than its amount or left available negative
+}
+
+impl ClientData {
+    ///add signed changes to available, held and total, all or nothing
+    fn adjust(&mut self, avai: Amount, held: Amount, total: Amount) -> Result<(), Rejection> {
+        let avai = self.avai.checked_add(avai).ok_or(Rejection::Overflow)?;
+        let held = self.held.checked_add(held).ok_or(Rejection::Overflow)?;
+        let total = self.total.checked_add(total).ok_or(Rejection::Overflow)?;
+        self.avai = avai;
+        self.held = held;
+        self.total = total;
+        Ok(())
+    }
 }
 
 impl From<(&Client, &ClientData)> for Output {
     fn from((client_, data): (&Client, &ClientData)) -> Self {
         Self {
             client: *client_,
+            currency: None,
             available: data.avai,
             held: data.held,
             total: data.total,
-            locked: data.locked,
+            locked: false,
+        }
+    }
+}
+
+pub(crate) type ClientMap = HashMap<Client, ClientData>;
+pub(crate) type Locks = HashSet<Client>;
+pub(crate) type Ledgers = HashMap<(Client, Currency), ClientData>;
+///tx ids are unique per client, what is kept of a tx is by its client and id
+pub(crate) type TxKey = (Client, Tx);
+pub(crate) type Currencies = HashMap<TxKey, Currency>;
+pub(crate) type TxIndex = HashMap<Client, HashSet<Tx>>;
+pub(crate) type Record = HashMap<TxKey, InputInternal>;
+pub(crate) type Shortfalls = HashMap<TxKey, Amount>;
+pub(crate) type History = HashMap<TxKey, Vec<Transition>>;
+pub(crate) type DisputeCounts = HashMap<TxKey, u32>;
+pub(crate) type Origins = HashMap<TxKey, InputAt>;
+pub(crate) type DisputedMap = HashMap<TxKey, DisputedAmounts>;
+
+///disputed funds of a tx
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct DisputedAmounts {
+    pub held: Amount,         //disputed by the pending dispute, zero if none is
+    pub charged_back: Amount, //reversed by a chargeback
+}
+
+///where and when an input was found in the source
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct InputAt {
+    pub position: u64,
+    pub timestamp: Option<u64>,
+}
+
+///dispute status a deposit or withdrawl entered and the position of the input that caused it
+#[derive(Debug, Serialize, Clone, Copy, Eq, PartialEq)]
+pub struct Transition {
+    pub client: Client,
+    pub tx: Tx,
+    pub status: DisputeStatus,
+    pub position: u64, //row number or other position in the input source
+}
+
+///what changed the lock of an account
+#[derive(Debug, Serialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    ChargebackLock, //locked by the chargeback of tx
+    AdminLock,      //locked by an admin request with reference tx
+    AdminUnlock,    //reinstated by an admin request with reference tx
+}
+
+///entry of the audit trail of account locks, in the order they happened per client
+#[derive(Debug, Serialize, Clone, Copy, Eq, PartialEq)]
+pub struct AuditEntry {
+    pub client: Client,
+    pub tx: Tx,
+    pub action: AuditAction,
+    pub position: u64, //of the input, row number for csv input, 0 if restored from a state without it
+}
+
+///client owing funds, with a negative available balance or a partial hold
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct Exposure {
+    pub client: Client,
+    pub currency: Option<Currency>, //None for the unnamed currency
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub shortfall: Amount, //disputed funds pending disputes could not hold
+    pub flagged: bool,
+    pub locked: bool,
+}
+
+///deposit or withdrawl targeted by a dispute, resolve or chargeback
+struct Target<'a> {
+    data: &'a mut ClientData,
+    kind: Disputed,
+    amount: Amount,
+    status: &'a mut DisputeStatus,
+    currency: Option<Currency>, //ledger of the tx
+    policy: &'a dyn DisputePolicy,
+    key: TxKey,
+    negative_balance: NegativeBalancePolicy,
+    shortfalls: &'a mut Shortfalls,
+    disputes: u32,            //times disputed so far
+    origin: Option<InputAt>,  //when the tx was applied, if kept
+    disputed: Option<Amount>, //amount under the pending dispute, the whole tx if not known
+}
+
+impl Target<'_> {
+    ///amount a resolve or chargeback acts on
+    fn outstanding(&self) -> Amount {
+        self.disputed.unwrap_or(self.amount)
+    }
+
+    ///whether another dispute at is allowed by limits
+    fn check_limits(&self, limits: DisputeLimits, at: InputAt) -> Result<(), Rejection> {
+        if limits.max_disputes.is_some_and(|n| self.disputes >= n) {
+            return Err(Rejection::TooManyDisputes);
         }
+        let closed = match (limits.window, self.origin) {
+            (Some(DisputeWindow::Inputs(n)), Some(origin)) => {
+                at.position.saturating_sub(origin.position) > n
+            }
+            (Some(DisputeWindow::Seconds(n)), Some(origin)) => {
+                match (origin.timestamp, at.timestamp) {
+                    (Some(start), Some(time)) => time.saturating_sub(start) > n,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                }
+            }
+            _ => false,
+        };
+        if closed {
+            return Err(Rejection::WindowClosed);
+        }
+        Ok(())
+    }
+
+    ///apply a dispute under the negative balance policy, returning the changes made
+    fn open(&mut self, (avai, held, total): Deltas) -> Result<Deltas, Rejection> {
+        let after = self
+            .data
+            .avai
+            .checked_add(avai)
+            .ok_or(Rejection::Overflow)?;
+        if after >= Amount::ZERO || avai >= Amount::ZERO {
+            self.data.adjust(avai, held, total)?;
+            return Ok((avai, held, total));
+        }
+        match self.negative_balance {
+            NegativeBalancePolicy::Allow => {
+                self.data.adjust(avai, held, total)?;
+                Ok((avai, held, total))
+            }
+            NegativeBalancePolicy::Reject => Err(Rejection::InsufficientFunds),
+            NegativeBalancePolicy::PartialHold => {
+                //hold what is available, nothing if available is already negative
+                let short = after.checked_neg().ok_or(Rejection::Overflow)?;
+                let short = short.min(avai.checked_neg().ok_or(Rejection::Overflow)?);
+                let avai = avai.checked_add(short).ok_or(Rejection::Overflow)?;
+                let held = held.checked_sub(short).ok_or(Rejection::Overflow)?;
+                self.data.adjust(avai, held, total)?;
+                self.shortfalls.insert(self.key, short);
+                self.data.flagged = true;
+                Ok((avai, held, total))
+            }
+        }
+    }
+
+    ///apply a resolve or chargeback, releasing only what the dispute held, returning
+    ///the changes made
+    fn close(&mut self, (avai, held, total): Deltas) -> Result<Deltas, Rejection> {
+        let short = self
+            .shortfalls
+            .get(&self.key)
+            .copied()
+            .unwrap_or(Amount::ZERO);
+        let avai = avai.checked_sub(short).ok_or(Rejection::Overflow)?;
+        let held = held.checked_add(short).ok_or(Rejection::Overflow)?;
+        let after = self
+            .data
+            .avai
+            .checked_add(avai)
+            .ok_or(Rejection::Overflow)?;
+        if after < Amount::ZERO && avai < Amount::ZERO {
+            match self.negative_balance {
+                NegativeBalancePolicy::Allow => {}
+                NegativeBalancePolicy::Reject => return Err(Rejection::InsufficientFunds),
+                NegativeBalancePolicy::PartialHold => self.data.flagged = true,
+            }
+        }
+        self.data.adjust(avai, held, total)?;
+        self.shortfalls.remove(&self.key);
+        Ok((avai, held, total))
     }
 }
 
@@ -27,153 +353,1032 @@ impl From<(&Client, &ClientData)> for Output {
 /// Can also possibly instantiate multiple executors on threads and
 /// partition work by mapping user id -> executor and use lockfree
 /// spsc queues (eg: crossbeam), but will keep it simple for now
-#[derive(Default)]
+///
+/// How disputes move funds is up to its `DisputePolicy`, `LegacyDisputes`
+/// unless set with `with_dispute_policy`.
+///
+/// Each currency is a ledger of its own, balances of the unnamed currency are
+/// kept with the client and those of named currencies apart. Locks are of the
+/// whole account and kept apart from balances.
+#[derive(Debug, Default)]
 pub struct Executor {
-    client_data: HashMap<Client, ClientData>,
-    client_record: HashMap<Client, HashSet<Tx>>, //record for only deposits and withdrawls
-    record: HashMap<Tx, InputInternal>,          //record for only deposits and withdrawls
+    client_data: ClientMap,              //unnamed currency balances
+    ledgers: Ledgers,                    //balances in named currencies
+    locks: Locks,                        //locked accounts
+    client_record: TxIndex,              //record for only deposits and withdrawls
+    record: Record,                      //record for only deposits and withdrawls
+    wal_seq: u64,                        //last write-ahead log entry applied, 0 if none
+    checkpoint: Option<Checkpoint>, //input position the state reflects, while a run is unfinished
+    shortfalls: Shortfalls,         //pending disputes holding less than their amount, by tx
+    audit: Vec<AuditEntry>,         //lock changes
+    history: Option<History>,       //dispute status changes by tx, only kept if asked for
+    journal: Option<Vec<JournalEntry>>, //entries of applied inputs, only kept if asked for
+    journal_error: Option<JournalError>, //first entry the journal could not take
+    violations: Vec<Violation>,     //broken invariants, only checked if asked for
+    position: u64,                  //position of the last input, process uses the next one
+    dispute_counts: DisputeCounts,  //times each tx was disputed, only txs disputed at least once
+    origins: Origins,               //when each tx was applied, kept while a dispute window is set
+    disputed: DisputedMap,          //disputed funds, only txs disputed at least once
+    currencies: Currencies,         //currency of txs in a named currency
+    policy: Policy,
+    negative_balance: NegativeBalancePolicy,
+    limits: DisputeLimits,
+    check_invariants: bool,
+}
+
+///shared dispute policy, defaulting to the legacy one
+#[derive(Debug, Clone)]
+struct Policy(Arc<dyn DisputePolicy>);
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy(Arc::new(LegacyDisputes))
+    }
+}
+
+impl std::ops::Deref for Policy {
+    type Target = dyn DisputePolicy;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+///balances an input may change and what they were before it with whether their
+///account was locked, to check its changes
+type Before = Vec<(Client, Option<Currency>, ClientData, bool)>;
+
+///changes to available, held and total an input made to each balance, by the
+///input and the dispute policy, to journal it. None if no journal is kept
+type Moves = Option<Vec<(Client, Option<Currency>, Deltas)>>;
+
+///executors are equal when their state is, policies, input position, journal and
+///invariant violations aside
+impl PartialEq for Executor {
+    fn eq(&self, other: &Self) -> bool {
+        self.client_data == other.client_data
+            && self.ledgers == other.ledgers
+            && self.locks == other.locks
+            && self.currencies == other.currencies
+            && self.client_record == other.client_record
+            && self.record == other.record
+            && self.wal_seq == other.wal_seq
+            && self.checkpoint == other.checkpoint
+            && self.shortfalls == other.shortfalls
+            && self.audit == other.audit
+            && self.history == other.history
+            && self.dispute_counts == other.dispute_counts
+            && self.origins == other.origins
+            && self.disputed == other.disputed
+    }
 }
 
 impl Executor {
+    ///use policy for disputes, resolves and chargebacks
+    pub fn with_dispute_policy<P: DisputePolicy + 'static>(mut self, policy: P) -> Self {
+        self.policy = Policy(Arc::new(policy));
+        self
+    }
+
+    ///how disputes that would take available below zero are handled
+    pub fn with_negative_balance_policy(mut self, policy: NegativeBalancePolicy) -> Self {
+        self.negative_balance = policy;
+        self
+    }
+
+    ///limit how often and how late a tx can be disputed
+    pub fn with_dispute_limits(mut self, limits: DisputeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    ///keep the dispute status history of every deposit and withdrawl, see `tx_history`
+    pub fn with_tx_history(mut self) -> Self {
+        self.history.get_or_insert_with(History::default);
+        self
+    }
+
+    /// Keep a double-entry journal of applied inputs, see `journal`
+    ///
+    /// The journal is not saved with snapshots, one kept for a state that
+    /// already has balances opens with an entry for them.
+    pub fn with_journal(mut self) -> Self {
+        if self.journal.is_none() {
+            let mut opening: Vec<_> = self
+                .balances()
+                .map(|(client, currency, x)| (client, currency, (x.avai, x.held, x.total)))
+                .collect();
+            opening.sort_by_key(|(client, currency, ..)| (client.0, *currency));
+            let mut journal = vec![];
+            match postings(opening) {
+                Some(x) if x.is_empty() => {}
+                Some(postings) => journal.push(JournalEntry {
+                    position: self.position,
+                    tx: None,
+                    applied: None,
+                    postings,
+                }),
+                None => {
+                    self.journal_error = Some(JournalError::Overflow {
+                        position: self.position,
+                        tx: None,
+                    })
+                }
+            }
+            self.journal = Some(journal);
+        }
+        self
+    }
+
+    /// Check the invariants of the balances an input changes after each one, see `violations`
+    ///
+    /// Made for debugging and audits, the pending disputes of a client are
+    /// gone through on each of its inputs. They are checked under the current
+    /// dispute policy, so a state restored with another one may break them.
+    pub fn with_invariant_checks(mut self) -> Self {
+        self.check_invariants = true;
+        self
+    }
+
     ///process an input
-    pub fn process(&mut self, input: Input) {
-        let input = InputInternal::from(input);
+    ///
+    ///balance arithmetic is checked, a rejected input leaves state untouched but for
+    ///a client first seen in a withdrawl it can't cover, which is listed with nothing
+    pub fn process(&mut self, input: Input) -> Result<Applied, Rejection> {
+        self.process_at(self.position + 1, input)
+    }
+
+    ///process an input found at position in the source, such as its row number
+    pub fn process_at(&mut self, position: u64, input: Input) -> Result<Applied, Rejection> {
+        self.position = position;
+        let at = InputAt {
+            position,
+            timestamp: input.timestamp,
+        };
+        self.apply(at, input.currency, InputInternal::try_from(input)?)
+    }
+
+    ///process an input, appending it to the write-ahead log before state changes
+    ///
+    ///inputs that fail validation are not logged, the outcome of the rest is
+    ///decided by the state so replaying the log reproduces it
+    pub fn process_logged<W: Write>(
+        &mut self,
+        position: u64,
+        input: Input,
+        wal: &mut Wal<W>,
+    ) -> io::Result<Result<Applied, Rejection>> {
+        let at = InputAt {
+            position,
+            timestamp: input.timestamp,
+        };
+        let currency = input.currency;
+        let input = match InputInternal::try_from(input) {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e.into())),
+        };
+        self.wal_seq = wal.append(at, currency, &input)?;
+        Ok(self.apply(at, currency, input))
+    }
+
+    ///last write-ahead log entry reflected in the state, 0 if none
+    pub fn wal_seq(&self) -> u64 {
+        self.wal_seq
+    }
+
+    pub(crate) fn set_wal_seq(&mut self, seq: u64) {
+        self.wal_seq = seq;
+    }
+
+    ///input position to resume from, saved with snapshots
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.checkpoint
+    }
+
+    ///mark the state as reflecting the input up to a checkpoint, None once the input is done
+    pub fn set_checkpoint(&mut self, checkpoint: Option<Checkpoint>) {
+        self.checkpoint = checkpoint;
+    }
+
+    pub(crate) fn apply(
+        &mut self,
+        at: InputAt,
+        currency: Option<Currency>,
+        input: InputInternal,
+    ) -> Result<Applied, Rejection> {
+        let position = at.position;
+        self.position = position;
+        let before = self.before(|x| x.touched(currency, &input));
+        let mut moves = self.journal.is_some().then(Vec::new);
+        let applied = self.apply_input(at, currency, input, &mut moves)?;
+        self.after(position, input, applied, before, moves);
+        if let Some(history) = self.history.as_mut() {
+            let status = match applied {
+                Applied::Deposit | Applied::Withdrawl | Applied::Resolve => DisputeStatus::Eligible,
+                Applied::Dispute => DisputeStatus::Pending,
+                Applied::Chargeback => DisputeStatus::Complete,
+                Applied::Lock | Applied::Unlock | Applied::Transfer => return Ok(applied),
+            };
+            let (client, tx) = key_of(&input);
+            history.entry((client, tx)).or_default().push(Transition {
+                client,
+                tx,
+                status,
+                position,
+            });
+        }
+        Ok(applied)
+    }
+
+    ///apply an input, adding what it changed to moves
+    fn apply_input(
+        &mut self,
+        at: InputAt,
+        currency: Option<Currency>,
+        input: InputInternal,
+        moves: &mut Moves,
+    ) -> Result<Applied, Rejection> {
         match input {
             InputInternal::Deposit(client, tx, amount, _eligible) => {
-                let txs = self.client_record.entry(client).or_default();
                 //duplicate tx shouldn't occur in input file anyways, so ignore it if it already exists
-                if !txs.contains(&tx) {
-                    txs.insert(tx);
-                    let mut data = self.client_data.entry(client).or_default();
-                    if !data.locked {
-                        data.avai.0 += amount.0;
-                        data.total.0 += amount.0;
-                        self.record.insert(tx, input);
-                    }
+                if self.known_tx(client, tx) {
+                    return Err(Rejection::DuplicateTx);
                 }
+                if self.locks.contains(&client) {
+                    return Err(Rejection::AccountLocked);
+                }
+                ledger(&mut self.client_data, &mut self.ledgers, client, currency).adjust(
+                    amount,
+                    Amount::ZERO,
+                    amount,
+                )?;
+                self.client_record.entry(client).or_default().insert(tx);
+                self.keep_tx(at, currency, input);
+                push_move(moves, (client, currency, (amount, Amount::ZERO, amount)));
+                Ok(Applied::Deposit)
             }
             InputInternal::Withdrawl(client, tx, amount, _eligible) => {
-                let txs = self.client_record.entry(client).or_default();
                 //duplicate tx shouldn't occur in input file anyways, so ignore it if it already exists
-                if !txs.contains(&tx) {
-                    let mut data = self.client_data.entry(client).or_default();
-                    if data.avai.0 < amount.0 || data.locked {
-                        //fail
-                    } else {
-                        txs.insert(tx);
-                        data.avai.0 -= amount.0;
-                        data.total.0 -= amount.0;
-                        self.record.insert(tx, input);
+                if self.known_tx(client, tx) {
+                    return Err(Rejection::DuplicateTx);
+                }
+                if self.locks.contains(&client) {
+                    return Err(Rejection::AccountLocked);
+                }
+                let avai = self
+                    .balance(client, currency)
+                    .map_or(Amount::ZERO, |x| x.avai);
+                if avai < amount {
+                    //a client first seen in a withdrawl it can't cover is listed with nothing,
+                    //named currency balances only exist once something was applied to them
+                    if currency.is_none() {
+                        self.client_data.entry(client).or_default();
                     }
+                    return Err(Rejection::InsufficientFunds);
                 }
+                let neg = amount.checked_neg().ok_or(Rejection::Overflow)?;
+                ledger(&mut self.client_data, &mut self.ledgers, client, currency).adjust(
+                    neg,
+                    Amount::ZERO,
+                    neg,
+                )?;
+                self.client_record.entry(client).or_default().insert(tx);
+                self.keep_tx(at, currency, input);
+                push_move(moves, (client, currency, (neg, Amount::ZERO, neg)));
+                Ok(Applied::Withdrawl)
             }
-            InputInternal::Dispute(client, tx) => {
+            InputInternal::Dispute(client, tx, part) => {
                 //only take first dispute of tx if there are multiple
-
-                if let Some(x) = self.record.get_mut(&tx) {
-                    match x {
-                        InputInternal::Deposit(client_, _tx, amount, dispute_status) => {
-                            if client == *client_
-                                && *dispute_status == DisputeStatus::Eligible
-                                && !self.client_data.get(&client).unwrap().locked
-                            {
-                                //undo a deposit may make the balance go into negative territory, just do it anyways since there isn't an explicit rule about it
-
-                                let data = self.client_data.entry(client).or_default();
-                                data.avai.0 -= amount.0;
-                                data.held.0 += amount.0;
-                                *dispute_status = DisputeStatus::Pending;
-                            }
-                        }
-                        InputInternal::Withdrawl(client_, _tx, amount, dispute_status) => {
-                            if client == *client_
-                                && *dispute_status == DisputeStatus::Eligible
-                                && !self.client_data.get(&client).unwrap().locked
-                            {
-                                let data = self.client_data.entry(client).or_default();
-                                data.avai.0 += amount.0;
-                                data.held.0 -= amount.0;
-                                *dispute_status = DisputeStatus::Pending;
-                            }
-                        }
-                        _ => { //ignore
-                        }
-                    }
+                let limits = self.limits;
+                let mut target = self.disputable(client, tx, currency)?;
+                if *target.status != DisputeStatus::Eligible {
+                    return Err(Rejection::AlreadyDisputed);
+                }
+                let amount = part.unwrap_or(target.amount);
+                if amount > target.amount {
+                    return Err(Rejection::DisputeTooLarge);
                 }
+                target.check_limits(limits, at)?;
+                //undo a deposit may make the balance go into negative territory, the negative balance policy decides
+                let deltas = target.policy.dispute(target.kind, amount)?;
+                push_move(moves, (client, target.currency, target.open(deltas)?));
+                *target.status = DisputeStatus::Pending;
+                *self.dispute_counts.entry((client, tx)).or_default() += 1;
+                self.disputed.entry((client, tx)).or_default().held = amount;
+                Ok(Applied::Dispute)
             }
             InputInternal::Resolve(client, tx) => {
-                if let Some(x) = self.record.get_mut(&tx) {
-                    match x {
-                        InputInternal::Deposit(client_, _tx, amount, dispute_status) => {
-                            if client == *client_
-                                && *dispute_status == DisputeStatus::Pending
-                                && !self.client_data.get(&client).unwrap().locked
-                            {
-                                let data = self.client_data.entry(client).or_default();
-                                data.avai.0 += amount.0;
-                                data.held.0 -= amount.0;
-                                *dispute_status = DisputeStatus::Eligible;
-                            }
-                        }
-                        InputInternal::Withdrawl(client_, _tx, amount, dispute_status) => {
-                            if client == *client_
-                                && *dispute_status == DisputeStatus::Pending
-                                && !self.client_data.get(&client).unwrap().locked
-                            {
-                                let data = self.client_data.entry(client).or_default();
-                                data.avai.0 -= amount.0;
-                                data.held.0 += amount.0;
-                                *dispute_status = DisputeStatus::Eligible;
-                            }
-                        }
-                        _ => { //ignore
-                        }
-                    }
+                let mut target = self.disputable(client, tx, currency)?;
+                if *target.status != DisputeStatus::Pending {
+                    return Err(Rejection::NotDisputed);
                 }
+                let deltas = target.policy.resolve(target.kind, target.outstanding())?;
+                push_move(moves, (client, target.currency, target.close(deltas)?));
+                *target.status = DisputeStatus::Eligible;
+                if let Some(x) = self.disputed.get_mut(&(client, tx)) {
+                    x.held = Amount::ZERO;
+                }
+                Ok(Applied::Resolve)
             }
             InputInternal::Chargeback(client, tx) => {
                 //undo deposit or withdrawl
-                if let Some(x) = self.record.get_mut(&tx) {
-                    match x {
-                        InputInternal::Deposit(client_, _tx, amount, dispute_status) => {
-                            if client == *client_
-                                && *dispute_status == DisputeStatus::Pending
-                                && !self.client_data.get(&client).unwrap().locked
-                            {
-                                //undo a deposit may make the balance go into negative territory, just do it anyways since there isn't an explicit rule about it
-
-                                let data = self.client_data.entry(client).or_default();
-                                data.held.0 -= amount.0;
-                                data.total.0 -= amount.0;
-                                *dispute_status = DisputeStatus::Complete;
-                                self.client_data.get_mut(&client).unwrap().locked = true;
-                            }
-                        }
-                        InputInternal::Withdrawl(client_, _tx, amount, dispute_status) => {
-                            if client == *client_
-                                && *dispute_status == DisputeStatus::Pending
-                                && !self.client_data.get(&client).unwrap().locked
-                            {
-                                let data = self.client_data.entry(client).or_default();
-                                data.held.0 += amount.0;
-                                data.total.0 += amount.0;
-                                *dispute_status = DisputeStatus::Complete;
-                                self.client_data.get_mut(&client).unwrap().locked = true;
-                            }
-                        }
-                        _ => {
-                            //ignore
-                        }
-                    }
+                let mut target = self.disputable(client, tx, currency)?;
+                if *target.status != DisputeStatus::Pending {
+                    return Err(Rejection::NotDisputed);
+                }
+                let amount = target.outstanding();
+                let deltas = target.policy.chargeback(target.kind, amount)?;
+                push_move(moves, (client, target.currency, target.close(deltas)?));
+                *target.status = DisputeStatus::Complete;
+                self.locks.insert(client);
+                let x = self.disputed.entry((client, tx)).or_default();
+                x.held = Amount::ZERO;
+                x.charged_back = amount;
+                self.audit.push(AuditEntry {
+                    client,
+                    tx,
+                    action: AuditAction::ChargebackLock,
+                    position: at.position,
+                });
+                Ok(Applied::Chargeback)
+            }
+            InputInternal::Lock(client, tx) => {
+                if !self.locks.insert(client) {
+                    return Err(Rejection::AlreadyLocked);
+                }
+                self.audit.push(AuditEntry {
+                    client,
+                    tx,
+                    action: AuditAction::AdminLock,
+                    position: at.position,
+                });
+                Ok(Applied::Lock)
+            }
+            InputInternal::Unlock(client, tx) => {
+                if !self.locks.remove(&client) {
+                    return Err(Rejection::NotLocked);
                 }
+                self.audit.push(AuditEntry {
+                    client,
+                    tx,
+                    action: AuditAction::AdminUnlock,
+                    position: at.position,
+                });
+                Ok(Applied::Unlock)
+            }
+            InputInternal::Transfer(from, to, tx, amount) => {
+                //both sides are checked before either changes so it applies whole or not at all
+                self.check_transfer_out(from, tx, amount, currency)?;
+                self.check_transfer_in(to, amount, currency)?;
+                push_move(moves, self.transfer_out(from, tx, amount, currency)?);
+                push_move(moves, self.transfer_in(to, amount, currency)?);
+                Ok(Applied::Transfer)
             }
         }
     }
 
-    ///return clients' data
-    pub fn output(&mut self) -> impl Iterator<Item = Output> + '_ {
+    ///record an applied deposit or withdrawl, its tx id is new for the client
+    fn keep_tx(&mut self, at: InputAt, currency: Option<Currency>, input: InputInternal) {
+        let key = key_of(&input);
+        self.record.insert(key, input);
+        if let Some(x) = currency {
+            self.currencies.insert(key, x);
+        }
+        if self.limits.window.is_some() {
+            self.origins.insert(key, at);
+        }
+    }
+
+    ///whether client already used tx
+    fn known_tx(&self, client: Client, tx: Tx) -> bool {
+        self.client_record
+            .get(&client)
+            .is_some_and(|x| x.contains(&tx))
+    }
+
+    ///whether the source of a transfer can send it, changing nothing
+    fn check_transfer_out(
+        &self,
+        client: Client,
+        tx: Tx,
+        amount: Amount,
+        currency: Option<Currency>,
+    ) -> Result<(), Rejection> {
+        if self.known_tx(client, tx) {
+            return Err(Rejection::DuplicateTx);
+        }
+        if self.locks.contains(&client) {
+            return Err(Rejection::AccountLocked);
+        }
+        let avai = self
+            .balance(client, currency)
+            .map_or(Amount::ZERO, |x| x.avai);
+        if avai < amount {
+            return Err(Rejection::InsufficientFunds);
+        }
+        Ok(())
+    }
+
+    ///whether the destination of a transfer can receive it, changing nothing
+    fn check_transfer_in(
+        &self,
+        client: Client,
+        amount: Amount,
+        currency: Option<Currency>,
+    ) -> Result<(), Rejection> {
+        if self.locks.contains(&client) {
+            return Err(Rejection::AccountLocked);
+        }
+        if let Some(x) = self.balance(client, currency) {
+            x.avai.checked_add(amount).ok_or(Rejection::Overflow)?;
+            x.total.checked_add(amount).ok_or(Rejection::Overflow)?;
+        }
+        Ok(())
+    }
+
+    ///debit the source of a checked transfer, transfers are not disputable so only the tx id is kept
+    fn transfer_out(
+        &mut self,
+        client: Client,
+        tx: Tx,
+        amount: Amount,
+        currency: Option<Currency>,
+    ) -> Result<(Client, Option<Currency>, Deltas), Rejection> {
+        let neg = amount.checked_neg().ok_or(Rejection::Overflow)?;
+        ledger(&mut self.client_data, &mut self.ledgers, client, currency).adjust(
+            neg,
+            Amount::ZERO,
+            neg,
+        )?;
+        self.client_record.entry(client).or_default().insert(tx);
+        Ok((client, currency, (neg, Amount::ZERO, neg)))
+    }
+
+    ///credit the destination of a checked transfer
+    fn transfer_in(
+        &mut self,
+        client: Client,
+        amount: Amount,
+        currency: Option<Currency>,
+    ) -> Result<(Client, Option<Currency>, Deltas), Rejection> {
+        ledger(&mut self.client_data, &mut self.ledgers, client, currency).adjust(
+            amount,
+            Amount::ZERO,
+            amount,
+        )?;
+        Ok((client, currency, (amount, Amount::ZERO, amount)))
+    }
+
+    ///balances an input may change
+    fn touched(
+        &self,
+        currency: Option<Currency>,
+        input: &InputInternal,
+    ) -> Vec<(Client, Option<Currency>)> {
+        match *input {
+            InputInternal::Deposit(client, ..) | InputInternal::Withdrawl(client, ..) => {
+                vec![(client, currency)]
+            }
+            InputInternal::Dispute(client, tx, _)
+            | InputInternal::Resolve(client, tx)
+            | InputInternal::Chargeback(client, tx) => {
+                vec![(client, self.currencies.get(&(client, tx)).copied())]
+            }
+            InputInternal::Lock(..) | InputInternal::Unlock(..) => vec![],
+            InputInternal::Transfer(from, to, ..) => vec![(from, currency), (to, currency)],
+        }
+    }
+
+    ///balances an input may change before it, None unless invariants are checked
+    fn before<F>(&self, keys: F) -> Option<Before>
+    where
+        F: FnOnce(&Self) -> Vec<(Client, Option<Currency>)>,
+    {
+        if !self.check_invariants {
+            return None;
+        }
+        let before = keys(self)
+            .into_iter()
+            .map(|(client, currency)| {
+                let data = self.balance(client, currency).copied().unwrap_or_default();
+                (client, currency, data, self.locks.contains(&client))
+            })
+            .collect();
+        Some(before)
+    }
+
+    ///check what an applied input changed since `before` and journal its moves
+    fn after(
+        &mut self,
+        position: u64,
+        input: InputInternal,
+        applied: Applied,
+        before: Option<Before>,
+        moves: Moves,
+    ) {
+        for (client, currency, before, locked) in before.into_iter().flatten() {
+            let after = self.balance(client, currency).copied().unwrap_or_default();
+            let mut broken = vec![];
+            if after.avai.checked_add(after.held) != Some(after.total) {
+                broken.push(Invariant::TotalIsAvailablePlusHeld);
+            }
+            if after.held < Amount::ZERO && !self.policy.negative_held() {
+                broken.push(Invariant::HeldNonNegative);
+            }
+            let amounts = |x: ClientData| (x.avai, x.held, x.total);
+            if locked && amounts(before) != amounts(after) {
+                broken.push(Invariant::LockedUnchanged);
+            }
+            if self.pending_held(client, currency) != Some(after.held) {
+                broken.push(Invariant::PendingHeld);
+            }
+            self.violations
+                .extend(broken.into_iter().map(|invariant| Violation {
+                    position,
+                    input,
+                    client,
+                    currency,
+                    invariant,
+                }));
+        }
+        if let (Some(journal), Some(moves)) = (self.journal.as_mut(), moves) {
+            let tx = Some(key_of(&input).1);
+            match postings(moves) {
+                Some(postings) => journal.push(JournalEntry {
+                    position,
+                    tx,
+                    applied: Some(applied),
+                    postings,
+                }),
+                None => {
+                    self.journal_error
+                        .get_or_insert(JournalError::Overflow { position, tx });
+                }
+            }
+        }
+    }
+
+    ///what the pending disputes of client in currency hold under the dispute policy,
+    ///None if the policy rejects one of them
+    fn pending_held(&self, client: Client, currency: Option<Currency>) -> Option<Amount> {
+        let mut held = Amount::ZERO;
+        for tx in self.client_record.get(&client).into_iter().flatten() {
+            let key = (client, *tx);
+            let (kind, amount) = match self.record.get(&key) {
+                Some(InputInternal::Deposit(_, _, amount, DisputeStatus::Pending)) => {
+                    (Disputed::Deposit, *amount)
+                }
+                Some(InputInternal::Withdrawl(_, _, amount, DisputeStatus::Pending)) => {
+                    (Disputed::Withdrawl, *amount)
+                }
+                _ => continue,
+            };
+            if self.currencies.get(&key).copied() != currency {
+                continue;
+            }
+            //the pending dispute may be of part of the tx
+            let amount = self
+                .disputed
+                .get(&key)
+                .map(|x| x.held)
+                .filter(|x| *x != Amount::ZERO)
+                .unwrap_or(amount);
+            let (_, deltas, _) = self.policy.dispute(kind, amount).ok()?;
+            let short = self.shortfalls.get(&key).copied().unwrap_or_default();
+            held = held.checked_add(deltas)?.checked_sub(short)?;
+        }
+        Some(held)
+    }
+
+    ///balances of client in currency if it has any
+    fn balance(&self, client: Client, currency: Option<Currency>) -> Option<&ClientData> {
+        match currency {
+            Some(x) => self.ledgers.get(&(client, x)),
+            None => self.client_data.get(&client),
+        }
+    }
+
+    ///source half of a transfer whose clients are on different executors: validate it and
+    ///check the source, changing nothing
+    ///
+    ///the destination then runs `transfer_destination` if this passed, and the source
+    ///`transfer_source_commit` if that passed too
+    pub(crate) fn transfer_source_check(
+        &mut self,
+        position: u64,
+        input: Input,
+    ) -> Result<(), Rejection> {
+        self.position = position;
+        let (from, _, tx, amount) = transfer_parts(input)?;
+        self.check_transfer_out(from, tx, amount, input.currency)
+    }
+
+    ///destination half of a transfer between executors: check and credit the destination
+    pub(crate) fn transfer_destination(
+        &mut self,
+        position: u64,
+        input: Input,
+    ) -> Result<(), Rejection> {
+        self.position = position;
+        let (from, to, tx, amount) = transfer_parts(input)?;
+        self.check_transfer_in(to, amount, input.currency)?;
+        let before = self.before(|_| vec![(to, input.currency)]);
+        let x = self.transfer_in(to, amount, input.currency)?;
+        let moves = self.journal.is_some().then(|| vec![x]);
+        //half a journal entry, merging the executors joins it with the source's
+        let input = InputInternal::Transfer(from, to, tx, amount);
+        self.after(position, input, Applied::Transfer, before, moves);
+        Ok(())
+    }
+
+    ///debit the source once both halves of a transfer between executors passed
+    pub(crate) fn transfer_source_commit(&mut self, input: Input) -> Result<(), Rejection> {
+        let (from, to, tx, amount) = transfer_parts(input)?;
+        let before = self.before(|_| vec![(from, input.currency)]);
+        let x = self.transfer_out(from, tx, amount, input.currency)?;
+        let moves = self.journal.is_some().then(|| vec![x]);
+        let input = InputInternal::Transfer(from, to, tx, amount);
+        self.after(self.position, input, Applied::Transfer, before, moves);
+        Ok(())
+    }
+
+    ///look up the deposit or withdrawl record targeted by a dispute, resolve or chargeback
+    fn disputable(
+        &mut self,
+        client: Client,
+        tx: Tx,
+        currency: Option<Currency>,
+    ) -> Result<Target<'_>, Rejection> {
+        let key = (client, tx);
+        let x = self.record.get_mut(&key).ok_or(Rejection::UnknownTx)?;
+        let (kind, amount, status) = match x {
+            InputInternal::Deposit(_, _, amount, status) => (Disputed::Deposit, *amount, status),
+            InputInternal::Withdrawl(_, _, amount, status) => {
+                (Disputed::Withdrawl, *amount, status)
+            }
+            _ => return Err(Rejection::UnknownTx),
+        };
+        let named = self.currencies.get(&key).copied();
+        if currency.is_some_and(|x| Some(x) != named) {
+            return Err(Rejection::CurrencyMismatch);
+        }
+        if self.locks.contains(&client) {
+            return Err(Rejection::AccountLocked);
+        }
+        //a recorded tx implies its balances exist
+        let data = match named {
+            Some(x) => self.ledgers.get_mut(&(client, x)),
+            None => self.client_data.get_mut(&client),
+        }
+        .ok_or(Rejection::UnknownTx)?;
+        Ok(Target {
+            data,
+            kind,
+            amount,
+            status,
+            currency: named,
+            policy: &*self.policy,
+            key,
+            negative_balance: self.negative_balance,
+            shortfalls: &mut self.shortfalls,
+            disputes: self.dispute_counts.get(&key).copied().unwrap_or(0),
+            origin: self.origins.get(&key).copied(),
+            disputed: self
+                .disputed
+                .get(&key)
+                .map(|x| x.held)
+                .filter(|x| *x != Amount::ZERO),
+        })
+    }
+
+    ///partition into n executors by client id modulo n, as ParallelExecutor shards inputs
+    pub fn split(self, n: usize) -> Vec<Executor> {
+        let mut parts: Vec<Executor> = (0..n)
+            .map(|_| Executor {
+                wal_seq: self.wal_seq,
+                checkpoint: self.checkpoint,
+                policy: self.policy.clone(),
+                negative_balance: self.negative_balance,
+                limits: self.limits,
+                history: self.history.as_ref().map(|_| History::default()),
+                journal: self.journal.as_ref().map(|_| vec![]),
+                position: self.position,
+                check_invariants: self.check_invariants,
+                ..Default::default()
+            })
+            .collect();
+        //entries so far stay together, merge puts them back in order
+        if let (Some(x), Some(part)) = (self.journal, parts.first_mut()) {
+            part.journal = Some(x);
+            part.journal_error = self.journal_error;
+        }
+        let shard = |client: &Client| client.0 as usize % n;
+        for (client, data) in self.client_data {
+            parts[shard(&client)].client_data.insert(client, data);
+        }
+        for (key, data) in self.ledgers {
+            parts[shard(&key.0)].ledgers.insert(key, data);
+        }
+        for client in self.locks {
+            parts[shard(&client)].locks.insert(client);
+        }
+        for (client, txs) in self.client_record {
+            parts[shard(&client)].client_record.insert(client, txs);
+        }
+        for x in self.audit {
+            parts[shard(&x.client)].audit.push(x);
+        }
+        for x in self.violations {
+            parts[shard(&x.client)].violations.push(x);
+        }
+        for (key, x) in self.record {
+            parts[shard(&key.0)].record.insert(key, x);
+        }
+        for (key, x) in self.currencies {
+            parts[shard(&key.0)].currencies.insert(key, x);
+        }
+        for (key, x) in self.shortfalls {
+            parts[shard(&key.0)].shortfalls.insert(key, x);
+        }
+        for (key, x) in self.dispute_counts {
+            parts[shard(&key.0)].dispute_counts.insert(key, x);
+        }
+        for (key, x) in self.origins {
+            parts[shard(&key.0)].origins.insert(key, x);
+        }
+        for (key, x) in self.disputed {
+            parts[shard(&key.0)].disputed.insert(key, x);
+        }
+        for (key, x) in self.history.into_iter().flatten() {
+            if let Some(part) = parts[shard(&key.0)].history.as_mut() {
+                part.insert(key, x);
+            }
+        }
+        parts
+    }
+
+    ///combine executors holding disjoint sets of clients
+    pub fn merge<I: IntoIterator<Item = Executor>>(parts: I) -> Executor {
+        let mut merged = Executor::default();
+        let mut journals = vec![];
+        for x in parts {
+            journals.extend(x.journal);
+            merged.journal_error = merged.journal_error.or(x.journal_error);
+            merged.client_data.extend(x.client_data);
+            merged.ledgers.extend(x.ledgers);
+            merged.locks.extend(x.locks);
+            merged.currencies.extend(x.currencies);
+            merged.client_record.extend(x.client_record);
+            merged.record.extend(x.record);
+            merged.wal_seq = merged.wal_seq.max(x.wal_seq);
+            merged.checkpoint = merged.checkpoint.or(x.checkpoint);
+            merged.shortfalls.extend(x.shortfalls);
+            merged.audit.extend(x.audit);
+            if let Some(h) = x.history {
+                merged
+                    .history
+                    .get_or_insert_with(History::default)
+                    .extend(h);
+            }
+            merged.position = merged.position.max(x.position);
+            merged.dispute_counts.extend(x.dispute_counts);
+            merged.origins.extend(x.origins);
+            merged.disputed.extend(x.disputed);
+            merged.limits = x.limits;
+            merged.policy = x.policy;
+            merged.negative_balance = x.negative_balance;
+            merged.violations.extend(x.violations);
+            merged.check_invariants = x.check_invariants;
+        }
+        merged.violations.sort_by_key(|x| x.position);
+        if !journals.is_empty() {
+            let (journal, error) = merge_journals(journals);
+            merged.journal = Some(journal);
+            merged.journal_error = merged.journal_error.or(error);
+        }
+        merged
+    }
+
+    pub(crate) fn parts(&self) -> (&ClientMap, &TxIndex, &Record, &Shortfalls) {
+        (
+            &self.client_data,
+            &self.client_record,
+            &self.record,
+            &self.shortfalls,
+        )
+    }
+
+    pub(crate) fn parts_mut(
+        &mut self,
+    ) -> (&mut ClientMap, &mut TxIndex, &mut Record, &mut Shortfalls) {
+        (
+            &mut self.client_data,
+            &mut self.client_record,
+            &mut self.record,
+            &mut self.shortfalls,
+        )
+    }
+
+    pub(crate) fn locks(&self) -> &Locks {
+        &self.locks
+    }
+
+    pub(crate) fn locks_mut(&mut self) -> &mut Locks {
+        &mut self.locks
+    }
+
+    pub(crate) fn currency_parts(&self) -> (&Ledgers, &Currencies) {
+        (&self.ledgers, &self.currencies)
+    }
+
+    pub(crate) fn currency_parts_mut(&mut self) -> (&mut Ledgers, &mut Currencies) {
+        (&mut self.ledgers, &mut self.currencies)
+    }
+
+    ///changes of account locks so far
+    pub fn audit(&self) -> &[AuditEntry] {
+        &self.audit
+    }
+
+    pub(crate) fn audit_mut(&mut self) -> &mut Vec<AuditEntry> {
+        &mut self.audit
+    }
+
+    ///dispute status changes of a deposit or withdrawl in order, starting with it being
+    ///applied, None if the tx is unknown or history is not kept
+    pub fn tx_history(&self, client: Client, tx: Tx) -> Option<&[Transition]> {
+        self.history
+            .as_ref()?
+            .get(&(client, tx))
+            .map(|x| x.as_slice())
+    }
+
+    ///dispute status changes of every tx, by client and tx and then in order,
+    ///empty if history is not kept
+    pub fn tx_histories(&self) -> Vec<Transition> {
+        let mut txs: Vec<_> = self.history.iter().flat_map(|x| x.iter()).collect();
+        txs.sort_by_key(|((client, tx), _)| (client.0, tx.0));
+        txs.into_iter()
+            .flat_map(|(_, x)| x.iter().copied())
+            .collect()
+    }
+
+    ///whether dispute status history is kept
+    pub fn tx_history_kept(&self) -> bool {
+        self.history.is_some()
+    }
+
+    pub(crate) fn history_mut(&mut self) -> &mut Option<History> {
+        &mut self.history
+    }
+
+    ///double-entry journal of the inputs applied since it was kept, None if it is not
+    pub fn journal(&self) -> Option<&[JournalEntry]> {
+        self.journal.as_deref()
+    }
+
+    pub(crate) fn journal_error(&self) -> Option<JournalError> {
+        self.journal_error
+    }
+
+    ///inputs that broke an invariant, in input order,
+    ///empty unless invariants are checked
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    ///funds held and charged back by disputes of a tx, None if it was never disputed
+    pub fn tx_disputed(&self, client: Client, tx: Tx) -> Option<DisputedAmounts> {
+        self.disputed.get(&(client, tx)).copied()
+    }
+
+    pub(crate) fn disputes(&self) -> (&DisputeCounts, &Origins, &DisputedMap) {
+        (&self.dispute_counts, &self.origins, &self.disputed)
+    }
+
+    pub(crate) fn disputes_mut(&mut self) -> (&mut DisputeCounts, &mut Origins, &mut DisputedMap) {
+        (
+            &mut self.dispute_counts,
+            &mut self.origins,
+            &mut self.disputed,
+        )
+    }
+
+    ///position of the last input processed
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(crate) fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    ///clients with a negative available balance, a partial hold or a flag, by client id
+    pub fn exposures(&self) -> Vec<Exposure> {
+        let mut owed: HashMap<(Client, Option<Currency>), Amount> = HashMap::new();
+        for (key, short) in &self.shortfalls {
+            let currency = self.currencies.get(key).copied();
+            let x = owed.entry((key.0, currency)).or_default();
+            *x = x.checked_add(*short).unwrap_or(Amount(i64::MAX));
+        }
+        let mut exposures: Vec<Exposure> = self
+            .balances()
+            .filter(|(client, currency, data)| {
+                data.avai < Amount::ZERO || data.flagged || owed.contains_key(&(*client, *currency))
+            })
+            .map(|(client, currency, data)| Exposure {
+                client,
+                currency,
+                available: data.avai,
+                held: data.held,
+                total: data.total,
+                shortfall: owed.get(&(client, currency)).copied().unwrap_or_default(),
+                flagged: data.flagged,
+                locked: self.locks.contains(&client),
+            })
+            .collect();
+        exposures.sort_by_key(|x| (x.client.0, x.currency));
+        exposures
+    }
+
+    ///balances by client and currency, None for the unnamed currency
+    pub(crate) fn balances(
+        &self,
+    ) -> impl Iterator<Item = (Client, Option<Currency>, &ClientData)> + '_ {
+        let named = self
+            .ledgers
+            .iter()
+            .map(|((client, currency), data)| (*client, Some(*currency), data));
         self.client_data
             .iter()
-            .map(|(client, data)| Output::from((client, data)))
+            .map(|(client, data)| (*client, None, data))
+            .chain(named)
+    }
+
+    ///return clients' data, one per client and currency
+    ///
+    ///a locked client without balances has a row of the unnamed currency
+    pub fn output(&mut self) -> impl Iterator<Item = Output> + '_ {
+        let balances = self.balances().map(|(client, currency, data)| Output {
+            currency,
+            locked: self.locks.contains(&client),
+            ..Output::from((&client, data))
+        });
+        let locked = self
+            .locks
+            .iter()
+            .filter(|client| !self.has_balances(**client))
+            .map(|client| Output {
+                locked: true,
+                ..Output::from((client, &ClientData::default()))
+            });
+        balances.chain(locked)
+    }
+
+    ///whether client has balances in any currency
+    fn has_balances(&self, client: Client) -> bool {
+        self.client_data.contains_key(&client) || self.ledgers.keys().any(|x| x.0 == client)
+    }
+}
+
+///client and tx an input names, the source for a transfer
+fn key_of(input: &InputInternal) -> TxKey {
+    match *input {
+        InputInternal::Deposit(client, tx, ..)
+        | InputInternal::Withdrawl(client, tx, ..)
+        | InputInternal::Dispute(client, tx, _)
+        | InputInternal::Resolve(client, tx)
+        | InputInternal::Chargeback(client, tx)
+        | InputInternal::Lock(client, tx)
+        | InputInternal::Unlock(client, tx)
+        | InputInternal::Transfer(client, _, tx, _) => (client, tx),
+    }
+}
+
+///from, to, tx and amount of a transfer input
+fn transfer_parts(input: Input) -> Result<(Client, Client, Tx, Amount), Rejection> {
+    match InputInternal::try_from(input)? {
+        InputInternal::Transfer(from, to, tx, amount) => Ok((from, to, tx, amount)),
+        _ => unreachable!("not a transfer"),
+    }
+}
+
+///balances of client in currency, created if missing
+///add a balance change to moves if they are kept
+fn push_move(moves: &mut Moves, x: (Client, Option<Currency>, Deltas)) {
+    if let Some(moves) = moves {
+        moves.push(x);
+    }
+}
+
+fn ledger<'a>(
+    client_data: &'a mut ClientMap,
+    ledgers: &'a mut Ledgers,
+    client: Client,
+    currency: Option<Currency>,
+) -> &'a mut ClientData {
+    match currency {
+        Some(x) => ledgers.entry((client, x)).or_default(),
+        None => client_data.entry(client).or_default(),
     }
 }
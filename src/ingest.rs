@@ -0,0 +1,241 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::compression::*;
+use crate::core::*;
+use crate::executor::*;
+
+///how rows that fail to parse are handled
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum IngestMode {
+    #[default]
+    Strict, //stop at the first bad row and report it
+    Lenient, //skip bad rows, keeping a record of them
+}
+
+///row that could not be read as an input
+#[derive(Debug)]
+pub struct RowError {
+    pub row: u64, //line number in the csv source, header is line 1
+    pub error: csv::Error,
+    pub amount_error: Option<AmountParseError>, //set when the amount column is the problem
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.row, self.error)
+    }
+}
+
+impl Error for RowError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+///position in a csv source just after a row, to resume reading from
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct Checkpoint {
+    pub byte: u64,   //offset of the next row
+    pub line: u64,   //line number of the next row, header is line 1
+    pub record: u64, //records read so far, header included
+}
+
+///csv settings for input files: fields are trimmed and trailing empty columns may be left out
+pub fn reader_builder() -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder.trim(csv::Trim::All).flexible(true);
+    builder
+}
+
+/// Reads inputs from csv, each tagged with its row number
+///
+/// In strict mode the first bad row is returned as an error and iteration ends.
+/// In lenient mode bad rows are skipped and can be inspected via `skipped`.
+/// Rows whose amount is a number but not a valid amount are rejected in either
+/// mode and can be inspected via `rejected`.
+pub struct InputReader<R> {
+    reader: csv::Reader<R>,
+    headers: csv::StringRecord,
+    amount_idx: Option<usize>,
+    record: csv::StringRecord,
+    mode: IngestMode,
+    skipped: Vec<RowError>,
+    rejected: Vec<RejectedInput>,
+    done: bool,
+    base: Checkpoint, //where the underlying reader starts in the source, for readers opened mid-file
+}
+
+impl InputReader<Box<dyn io::Read + Send>> {
+    ///gzip and zstd files are decompressed on the fly
+    pub fn from_path<P: AsRef<Path>>(path: P, mode: IngestMode) -> Result<Self, csv::Error> {
+        Self::from_reader(open_input(path)?, mode)
+    }
+
+    ///continue reading a file at a checkpoint
+    ///
+    ///plain files are seeked, compressed ones are read up to the checkpoint
+    pub fn resume<P: AsRef<Path>>(
+        path: P,
+        mode: IngestMode,
+        checkpoint: Checkpoint,
+    ) -> Result<Self, csv::Error> {
+        let path = path.as_ref();
+        if Compression::of_file(path)? != Compression::None {
+            let mut reader = Self::from_path(path, mode)?;
+            reader.skip_to(checkpoint.line)?;
+            return Ok(reader);
+        }
+        let headers = reader_builder().from_path(path)?.headers()?.clone();
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(checkpoint.byte))?;
+        let mut reader =
+            Self::headerless(Box::new(file) as Box<dyn io::Read + Send>, headers, mode);
+        reader.base = Checkpoint {
+            line: checkpoint.line.saturating_sub(1),
+            ..checkpoint
+        };
+        Ok(reader)
+    }
+}
+
+impl<R: io::Read> InputReader<R> {
+    pub fn from_reader(rdr: R, mode: IngestMode) -> Result<Self, csv::Error> {
+        Self::new(reader_builder().from_reader(rdr), mode)
+    }
+
+    fn new(mut reader: csv::Reader<R>, mode: IngestMode) -> Result<Self, csv::Error> {
+        let headers = reader.headers()?.clone();
+        Ok(Self::with_headers(reader, headers, mode))
+    }
+
+    ///read a part of a csv source that has no header row of its own, rows are numbered from 1
+    pub(crate) fn headerless(rdr: R, headers: csv::StringRecord, mode: IngestMode) -> Self {
+        let reader = reader_builder().has_headers(false).from_reader(rdr);
+        Self::with_headers(reader, headers, mode)
+    }
+
+    fn with_headers(reader: csv::Reader<R>, headers: csv::StringRecord, mode: IngestMode) -> Self {
+        let amount_idx = headers.iter().position(|x| x == "amount");
+        Self {
+            reader,
+            headers,
+            amount_idx,
+            record: csv::StringRecord::new(),
+            mode,
+            skipped: vec![],
+            rejected: vec![],
+            done: false,
+            base: Checkpoint::default(),
+        }
+    }
+
+    ///position after the last row read
+    pub fn checkpoint(&self) -> Checkpoint {
+        let pos = self.reader.position();
+        Checkpoint {
+            byte: self.base.byte + pos.byte(),
+            line: self.base.line + pos.line(),
+            record: self.base.record + pos.record(),
+        }
+    }
+
+    ///read past rows before the given line without parsing them as inputs
+    pub fn skip_to(&mut self, line: u64) -> Result<(), csv::Error> {
+        while self.checkpoint().line < line {
+            if !self.reader.read_record(&mut self.record)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    ///rows skipped so far in lenient mode
+    pub fn skipped(&self) -> &[RowError] {
+        &self.skipped
+    }
+
+    ///take the rows skipped so far in lenient mode
+    pub fn take_skipped(&mut self) -> Vec<RowError> {
+        std::mem::take(&mut self.skipped)
+    }
+
+    ///rows rejected so far for their amount, in row order
+    pub fn rejected(&self) -> &[RejectedInput] {
+        &self.rejected
+    }
+
+    ///take the rows rejected so far for their amount, they come before the last row read
+    pub fn take_rejected(&mut self) -> Vec<RejectedInput> {
+        std::mem::take(&mut self.rejected)
+    }
+
+    ///the next row, None within if it was rejected for its amount
+    fn read_next(&mut self) -> Option<Result<Option<(u64, Input)>, RowError>> {
+        let res = self.reader.read_record(&mut self.record);
+        let row = self.base.line
+            + match self.record.position() {
+                Some(x) => x.line(),
+                None => self.reader.position().line(),
+            };
+        match res {
+            Ok(false) => None,
+            Ok(true) => match self.record.deserialize::<InputRow>(Some(&self.headers)) {
+                Ok(x) => match x.checked() {
+                    (input, None) => Some(Ok(Some((row, input)))),
+                    (input, Some((reason, amount))) => {
+                        self.rejected
+                            .push(RejectedInput::of_amount(row, input, reason, amount));
+                        Some(Ok(None))
+                    }
+                },
+                Err(error) => Some(Err(RowError {
+                    row,
+                    error,
+                    amount_error: self
+                        .amount_idx
+                        .and_then(|i| self.record.get(i))
+                        .filter(|x| !x.is_empty())
+                        .and_then(|x| x.parse::<Amount>().err()),
+                })),
+            },
+            Err(error) => {
+                let row = error.position().map_or(row, |x| self.base.line + x.line());
+                //io errors can't be skipped over
+                if let csv::ErrorKind::Io(_) = error.kind() {
+                    self.done = true;
+                }
+                Some(Err(RowError {
+                    row,
+                    error,
+                    amount_error: None,
+                }))
+            }
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for InputReader<R> {
+    type Item = Result<(u64, Input), RowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.read_next() {
+                None => self.done = true,
+                Some(Ok(Some(x))) => return Some(Ok(x)),
+                Some(Ok(None)) => {}
+                Some(Err(err)) => {
+                    if self.done || self.mode == IngestMode::Strict {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                    self.skipped.push(err);
+                }
+            }
+        }
+        None
+    }
+}
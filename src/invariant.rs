@@ -0,0 +1,50 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::core::*;
+
+///invariant of client balances checked after every input in invariant checking mode
+#[derive(Debug, Serialize, Clone, Copy, Hash, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Invariant {
+    TotalIsAvailablePlusHeld,
+    HeldNonNegative, //unless the dispute policy holds withdrawl disputes negative
+    LockedUnchanged, //balances of a locked account
+    PendingHeld,     //held is what the pending disputes of the client hold under the policy
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Invariant::TotalIsAvailablePlusHeld => "total is not available plus held",
+            Invariant::HeldNonNegative => "held is negative",
+            Invariant::LockedUnchanged => "locked account changed",
+            Invariant::PendingHeld => "held doesn't match pending disputes",
+        };
+        f.write_str(msg)
+    }
+}
+
+///input after which the balances of a client broke an invariant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Violation {
+    pub position: u64, //of the input, row number for csv input
+    pub input: InputInternal,
+    pub client: Client,
+    pub currency: Option<Currency>, //None for the unnamed currency
+    pub invariant: Invariant,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} for client {}", self.invariant, self.client.0)?;
+        if let Some(x) = self.currency {
+            write!(f, " in {}", x)?;
+        }
+        write!(f, " after input at {}: {:?}", self.position, self.input)
+    }
+}
+
+impl Error for Violation {}
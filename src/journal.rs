@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::core::*;
+use crate::executor::*;
+use crate::policy::*;
+
+///kind of account a posting goes to
+#[derive(Debug, Serialize, Clone, Copy, Hash, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountKind {
+    Omnibus,   //funds held for all clients, one per currency
+    Available, //of a client
+    Held,      //of a client
+}
+
+///account of the journal
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct Account {
+    pub kind: AccountKind,
+    pub client: Option<Client>, //None for the omnibus account
+    pub currency: Option<Currency>,
+}
+
+///amount posted to an account, a debit if positive and a credit if negative
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Posting {
+    pub account: Account,
+    pub amount: Amount,
+}
+
+/// Balanced journal entry of an applied input
+///
+/// Client accounts are what the system owes its clients: a deposit debits the
+/// omnibus account and credits the client's available balance, a dispute of
+/// it debits available and credits held and a chargeback debits held and
+/// credits the omnibus account. The postings of an entry sum to zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub position: u64,
+    pub tx: Option<Tx>,           //None for the opening balances
+    pub applied: Option<Applied>, //None for the opening balances
+    pub postings: Vec<Posting>,
+}
+
+impl JournalEntry {
+    ///sum of the postings, zero if the entry balances, None on overflow
+    pub fn sum(&self) -> Option<Amount> {
+        self.postings
+            .iter()
+            .try_fold(Amount::ZERO, |sum, x| sum.checked_add(x.amount))
+    }
+
+    ///one line per posting, for csv reports
+    pub fn lines(&self) -> impl Iterator<Item = JournalLine> + '_ {
+        self.postings.iter().map(|x| JournalLine {
+            position: self.position,
+            tx: self.tx,
+            applied: self.applied,
+            account: x.account.kind,
+            client: x.account.client,
+            currency: x.account.currency,
+            amount: x.amount,
+        })
+    }
+}
+
+///posting with its entry, as written to csv
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct JournalLine {
+    pub position: u64,
+    pub tx: Option<Tx>,
+    pub applied: Option<Applied>,
+    pub account: AccountKind,
+    pub client: Option<Client>,
+    pub currency: Option<Currency>,
+    pub amount: Amount,
+}
+
+///why a journal doesn't reconcile with the state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JournalError {
+    NotKept,
+    Unbalanced {
+        position: u64,
+        tx: Option<Tx>,
+    },
+    //postings of an entry, or the balances they add up to, out of range of Amount
+    Overflow {
+        position: u64,
+        tx: Option<Tx>,
+    },
+    Mismatch {
+        account: Account,
+        journal: Amount, //balance by the journal, credits positive
+        state: Amount,   //balance of the client
+    },
+    Total {
+        client: Client,
+        currency: Option<Currency>,
+        total: Amount, //not available plus held
+    },
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::NotKept => f.write_str("no journal is kept"),
+            JournalError::Unbalanced { position, tx } => {
+                write!(
+                    f,
+                    "journal entry at {} for tx {:?} is unbalanced",
+                    position, tx
+                )
+            }
+            JournalError::Overflow { position, tx } => {
+                write!(f, "journal entry at {} for tx {:?} overflows", position, tx)
+            }
+            JournalError::Mismatch {
+                account,
+                journal,
+                state,
+            } => write!(
+                f,
+                "{:?} account of client {:?} in {:?} is {} by the journal but {}",
+                account.kind, account.client, account.currency, journal, state
+            ),
+            JournalError::Total {
+                client,
+                currency,
+                total,
+            } => write!(
+                f,
+                "total {} of client {:?} in {:?} is not available plus held",
+                total, client, currency
+            ),
+        }
+    }
+}
+
+impl Error for JournalError {}
+
+///postings for changes to available, held and total of balances, client accounts
+///first then the omnibus account of each currency for the change in total,
+///None on overflow
+pub(crate) fn postings<I>(moves: I) -> Option<Vec<Posting>>
+where
+    I: IntoIterator<Item = (Client, Option<Currency>, Deltas)>,
+{
+    let mut postings = vec![];
+    let mut omnibus: Vec<(Option<Currency>, Amount)> = vec![];
+    for (client, currency, (avai, held, total)) in moves {
+        for (kind, amount) in [(AccountKind::Available, avai), (AccountKind::Held, held)] {
+            if amount != Amount::ZERO {
+                //a client account grows by a credit
+                postings.push(Posting {
+                    account: Account {
+                        kind,
+                        client: Some(client),
+                        currency,
+                    },
+                    amount: amount.checked_neg()?,
+                });
+            }
+        }
+        match omnibus.iter_mut().find(|x| x.0 == currency) {
+            Some((_, sum)) => *sum = sum.checked_add(total)?,
+            None => omnibus.push((currency, total)),
+        }
+    }
+    for (currency, amount) in omnibus {
+        if amount != Amount::ZERO {
+            postings.push(Posting {
+                account: Account {
+                    kind: AccountKind::Omnibus,
+                    client: None,
+                    currency,
+                },
+                amount,
+            });
+        }
+    }
+    Some(postings)
+}
+
+///combine the journals of executors split by client, in position order
+///
+///each executor journals its half of a transfer between them against the
+///omnibus account, the halves are joined back into one entry where that cancels out.
+///halves that overflow when joined are kept apart and the first is reported
+pub(crate) fn merge_journals<I: IntoIterator<Item = Vec<JournalEntry>>>(
+    parts: I,
+) -> (Vec<JournalEntry>, Option<JournalError>) {
+    let mut entries: Vec<JournalEntry> = parts.into_iter().flatten().collect();
+    entries.sort_by_key(|x| x.position);
+    let mut merged: Vec<JournalEntry> = Vec::with_capacity(entries.len());
+    let mut error = None;
+    for x in entries {
+        match merged.last_mut() {
+            Some(last)
+                if last.position == x.position
+                    && last.tx == x.tx
+                    && x.applied == Some(Applied::Transfer) =>
+            {
+                match join(&last.postings, &x.postings) {
+                    Some(postings) => last.postings = postings,
+                    None => {
+                        error.get_or_insert(JournalError::Overflow {
+                            position: x.position,
+                            tx: x.tx,
+                        });
+                        merged.push(x);
+                    }
+                }
+            }
+            _ => merged.push(x),
+        }
+    }
+    (merged, error)
+}
+
+///postings of two halves of an entry summed by account, None on overflow
+fn join(a: &[Posting], b: &[Posting]) -> Option<Vec<Posting>> {
+    let mut postings: Vec<Posting> = vec![];
+    for p in a.iter().chain(b) {
+        match postings.iter_mut().find(|x| x.account == p.account) {
+            Some(x) => x.amount = x.amount.checked_add(p.amount)?,
+            None => postings.push(*p),
+        }
+    }
+    postings.retain(|x| x.amount != Amount::ZERO);
+    Some(postings)
+}
+
+impl Executor {
+    ///check the journal against the state
+    ///
+    ///every entry must balance, the balances the journal gives client accounts
+    ///must be those of the clients, and each total must be available plus held.
+    ///the omnibus accounts then hold the sum of the totals as everything sums
+    ///to zero
+    pub fn check_journal(&self) -> Result<(), JournalError> {
+        let journal = self.journal().ok_or(JournalError::NotKept)?;
+        if let Some(e) = self.journal_error() {
+            return Err(e);
+        }
+        //client accounts have credit balances, so credits are summed
+        let mut balances: HashMap<Account, Amount> = HashMap::new();
+        for entry in journal {
+            let overflow = JournalError::Overflow {
+                position: entry.position,
+                tx: entry.tx,
+            };
+            match entry.sum() {
+                Some(Amount::ZERO) => {}
+                Some(_) => {
+                    return Err(JournalError::Unbalanced {
+                        position: entry.position,
+                        tx: entry.tx,
+                    })
+                }
+                None => return Err(overflow),
+            }
+            for x in &entry.postings {
+                let balance = balances.entry(x.account).or_default();
+                *balance = x
+                    .amount
+                    .checked_neg()
+                    .and_then(|x| balance.checked_add(x))
+                    .ok_or(overflow)?;
+            }
+        }
+
+        for (client, currency, data) in self.balances() {
+            for (kind, state) in [
+                (AccountKind::Available, data.avai),
+                (AccountKind::Held, data.held),
+            ] {
+                let account = Account {
+                    kind,
+                    client: Some(client),
+                    currency,
+                };
+                let journal = balances.remove(&account).unwrap_or_default();
+                if journal != state {
+                    return Err(JournalError::Mismatch {
+                        account,
+                        journal,
+                        state,
+                    });
+                }
+            }
+            if data.avai.checked_add(data.held) != Some(data.total) {
+                return Err(JournalError::Total {
+                    client,
+                    currency,
+                    total: data.total,
+                });
+            }
+        }
+        //accounts of clients without balances
+        for (account, amount) in balances {
+            if account.kind != AccountKind::Omnibus && amount != Amount::ZERO {
+                return Err(JournalError::Mismatch {
+                    account,
+                    journal: amount,
+                    state: Amount::ZERO,
+                });
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,25 @@
+mod compression;
 mod core;
 mod executor;
+mod ingest;
+mod invariant;
+mod journal;
+mod parallel;
+mod policy;
+mod snapshot;
+mod wal;
 
 mod interface {
+    pub use crate::compression::*;
     pub use crate::core::*;
     pub use crate::executor::*;
+    pub use crate::ingest::*;
+    pub use crate::invariant::*;
+    pub use crate::journal::*;
+    pub use crate::parallel::*;
+    pub use crate::policy::*;
+    pub use crate::snapshot::*;
+    pub use crate::wal::*;
 }
 
 pub use interface::*;
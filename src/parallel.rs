@@ -0,0 +1,713 @@
+use crossbeam::channel::{bounded, unbounded, Receiver, Sender, TrySendError};
+use crossbeam::thread;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::compression::*;
+use crate::core::*;
+use crate::executor::*;
+use crate::ingest::*;
+
+enum Msg {
+    Batch(Vec<(u64, Input)>), //row numbers and their inputs, in order
+    End,
+}
+
+///inputs of one file chunk for one worker
+struct ChunkMsg {
+    lines: u64,                //lines in the whole chunk, to number rows of later chunks
+    inputs: Vec<(u64, Input)>, //line within the chunk counting from 1, and its input
+}
+
+///chunks a parser may queue ahead for each worker
+const CHUNKS_IN_FLIGHT: usize = 2;
+
+/// Pool of executors on worker threads
+///
+/// Inputs are partitioned by client id modulo worker count so that all inputs
+/// of a client are processed in order by the same executor. The caller's
+/// thread feeds the workers and results are merged once the inputs run out.
+///
+/// A transfer between clients of different workers goes to both. The source's
+/// worker checks its side and tells the destination's, which checks its own,
+/// credits if both passed and answers, then the source debits if it did, so
+/// the transfer applies whole or not at all. Each worker handles its transfers
+/// in input order, so the earliest one waited on can always complete.
+///
+/// Inputs are handed over in per-worker batches to keep channel overhead
+/// small next to the work done per input. Worker queues are bounded, when a
+/// worker falls behind the feeding thread blocks until there is room again so
+/// memory stays flat on large inputs.
+#[derive(Debug, Clone, Copy)]
+pub struct ParallelExecutor {
+    num_workers: usize,
+    queue_capacity: usize, //batches per worker
+    batch_size: usize,
+    parser_threads: usize, //for run_file
+    chunk_size: u64,       //bytes per file chunk for run_file
+}
+
+impl Default for ParallelExecutor {
+    ///one worker per core
+    fn default() -> Self {
+        Self::new(num_cpus::get())
+    }
+}
+
+///how a worker's queue was fed during a run
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ShardStats {
+    pub inputs: u64,
+    pub batches: u64,
+    pub stalls: u64,       //batch sends that found the queue full
+    pub stalled: Duration, //time the feeding threads spent blocked on this queue
+}
+
+///merged result of a parallel run
+#[derive(Debug, Default)]
+pub struct ParallelOutput {
+    pub outputs: Vec<Output>,
+    pub rejections: Vec<RejectedInput>, //in row order
+    pub stats: Vec<ShardStats>,         //indexed by worker
+    pub skipped: Vec<RowError>,         //rows skipped by run_file in lenient mode, in row order
+    pub executors: Vec<Executor>,       //final state of each worker
+}
+
+impl ParallelOutput {
+    ///final state of all workers combined, to snapshot or to resume from
+    pub fn into_executor(self) -> Executor {
+        Executor::merge(self.executors)
+    }
+}
+
+///channels between workers to settle transfers, by worker index
+struct Links {
+    index: usize, //of this worker
+    senders: Vec<Sender<Result<(), Rejection>>>,
+    receivers: Vec<Receiver<Result<(), Rejection>>>,
+}
+
+///links of each of n workers
+fn links(n: usize) -> Vec<Links> {
+    let mut links: Vec<Links> = (0..n)
+        .map(|index| Links {
+            index,
+            senders: vec![],
+            receivers: vec![],
+        })
+        .collect();
+    for from in 0..n {
+        for to in 0..n {
+            //at most a check and an answer are in flight between two workers
+            let (sender, receiver) = unbounded();
+            links[from].senders.push(sender);
+            links[to].receivers.push(receiver);
+        }
+    }
+    links
+}
+
+///worker of an input's client, and for a transfer between workers that of the client it goes to
+fn workers_of(input: &Input, num_workers: usize) -> (usize, Option<usize>) {
+    let idx = input.client.0 as usize % num_workers;
+    let to = match (input.ty, input.to) {
+        (InputType::Transfer, Some(to)) => Some(to.0 as usize % num_workers).filter(|x| *x != idx),
+        _ => None,
+    };
+    (idx, to)
+}
+
+///executor of one worker and what it rejected
+struct Shard {
+    executor: Executor,
+    rejected: Vec<RejectedInput>,
+    links: Links,
+}
+
+impl Shard {
+    fn new(executor: Executor, links: Links) -> Self {
+        Self {
+            executor,
+            rejected: vec![],
+            links,
+        }
+    }
+
+    fn process(&mut self, row: u64, input: Input) {
+        if let (from, Some(to)) = workers_of(&input, self.links.senders.len()) {
+            if from == self.links.index {
+                self.transfer_source(row, input, to);
+            } else {
+                self.transfer_destination(row, input, from);
+            }
+            return;
+        }
+        if let Err(reason) = self.executor.process_at(row, input) {
+            self.rejected
+                .push(RejectedInput::from((row, input, reason)));
+        }
+    }
+
+    ///source side of a transfer to another worker, which records nothing if it is rejected
+    fn transfer_source(&mut self, row: u64, input: Input, peer: usize) {
+        let check = self.executor.transfer_source_check(row, input);
+        //the destination waits for the check either way but only answers a passed one
+        let _ = self.links.senders[peer].send(check);
+        let result = match check {
+            Ok(()) => match self.links.receivers[peer].recv() {
+                Ok(x) => x.and_then(|()| self.executor.transfer_source_commit(input)),
+                //the peer stopped after a failure, the output is discarded then
+                Err(_) => return,
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(reason) = result {
+            self.rejected
+                .push(RejectedInput::from((row, input, reason)));
+        }
+    }
+
+    ///destination side of a transfer from another worker
+    fn transfer_destination(&mut self, row: u64, input: Input, peer: usize) {
+        self.executor.set_position(row);
+        if let Ok(Ok(())) = self.links.receivers[peer].recv() {
+            let result = self.executor.transfer_destination(row, input);
+            let _ = self.links.senders[peer].send(result);
+        }
+    }
+
+    ///hang up on the other workers so none waits on this one
+    fn finish(mut self) -> Self {
+        self.links.senders.clear();
+        self
+    }
+}
+
+///what a parser thread of run_file saw, by chunk index
+#[derive(Default)]
+struct Parsed {
+    lines: Vec<(usize, u64)>,
+    skipped: Vec<(usize, RowError)>,
+    rejected: Vec<(usize, RejectedInput)>,
+    error: Option<(usize, RowError)>,
+    stats: Vec<ShardStats>,
+}
+
+impl ParallelExecutor {
+    pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+    pub const DEFAULT_BATCH_SIZE: usize = 256;
+    pub const DEFAULT_CHUNK_SIZE: u64 = 1 << 20;
+
+    ///as many file parser threads as workers
+    pub fn new(num_workers: usize) -> Self {
+        assert!(num_workers > 0, "need at least one worker");
+        Self {
+            num_workers,
+            queue_capacity: Self::DEFAULT_QUEUE_CAPACITY,
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            parser_threads: num_workers,
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    ///bound each worker's queue to capacity batches, 0 hands each batch over directly
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity;
+        self
+    }
+
+    ///number of inputs grouped per message to a worker, 1 sends inputs one by one
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be at least 1");
+        self.batch_size = batch_size;
+        self
+    }
+
+    ///threads parsing the input file in run_file
+    pub fn with_parser_threads(mut self, parser_threads: usize) -> Self {
+        assert!(parser_threads > 0, "need at least one parser thread");
+        self.parser_threads = parser_threads;
+        self
+    }
+
+    ///approximate size in bytes of the file ranges handed to parser threads in run_file
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        assert!(chunk_size > 0, "chunk size must be at least 1");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.num_workers
+    }
+
+    ///process inputs from any iterator, including a crossbeam Receiver<Input>
+    ///
+    ///rows are numbered from 1 in iteration order
+    pub fn run<I>(&self, inputs: I) -> ParallelOutput
+    where
+        I: IntoIterator<Item = Input>,
+    {
+        let rows = (1..).zip(inputs).map(Ok::<_, std::convert::Infallible>);
+        match self.try_run(rows) {
+            Ok(x) => x,
+            Err(never) => match never {},
+        }
+    }
+
+    ///process inputs tagged with their row number, stopping at the first error
+    ///
+    ///inputs already handed to the workers before an error are still processed
+    ///but the error is returned instead of their output
+    pub fn try_run<I, E>(&self, inputs: I) -> Result<ParallelOutput, E>
+    where
+        I: IntoIterator<Item = Result<(u64, Input), E>>,
+    {
+        self.try_run_from(Executor::default(), inputs)
+    }
+
+    ///as try_run, continuing from the state of an executor
+    pub fn try_run_from<I, E>(&self, state: Executor, inputs: I) -> Result<ParallelOutput, E>
+    where
+        I: IntoIterator<Item = Result<(u64, Input), E>>,
+    {
+        let num_workers = self.num_workers;
+
+        //concurrent msg channels for workers
+        let mut channels_sender: Vec<Sender<Msg>> = vec![];
+        let mut channels_receiver: Vec<Receiver<Msg>> = vec![];
+        for _ in 0..num_workers {
+            let (sender, receiver) = bounded(self.queue_capacity);
+            channels_sender.push(sender);
+            channels_receiver.push(receiver);
+        }
+
+        let (read_result, finished) = thread::scope(|s| {
+            let handles: Vec<_> = channels_receiver
+                .into_iter()
+                .zip(state.split(num_workers))
+                .zip(links(num_workers))
+                .map(|((receiver, executor), links)| {
+                    s.spawn(move |_| {
+                        let mut shard = Shard::new(executor, links);
+                        loop {
+                            match receiver.recv() {
+                                Ok(Msg::Batch(batch)) => {
+                                    for (row, input) in batch {
+                                        shard.process(row, input);
+                                    }
+                                }
+                                Ok(Msg::End) => break,
+                                Err(_) => panic!("receiver failure"),
+                            }
+                        }
+                        shard.finish()
+                    })
+                })
+                .collect();
+
+            let batch_size = self.batch_size;
+            let mut batches: Vec<Vec<(u64, Input)>> = (0..num_workers)
+                .map(|_| Vec::with_capacity(batch_size))
+                .collect();
+            let mut stats = vec![ShardStats::default(); num_workers];
+            let mut read_result = Ok(());
+            for result in inputs {
+                match result {
+                    Ok((row, input)) => {
+                        //client must be mapped to a same worker in order for result to be correct,
+                        //a transfer between workers goes to both at once as each waits on the other
+                        let (idx, peer) = workers_of(&input, num_workers);
+                        for idx in std::iter::once(idx).chain(peer) {
+                            batches[idx].push((row, input));
+                            if batches[idx].len() == batch_size || peer.is_some() {
+                                let batch = std::mem::replace(
+                                    &mut batches[idx],
+                                    Vec::with_capacity(batch_size),
+                                );
+                                let n = batch.len();
+                                let sent = send(
+                                    &channels_sender[idx],
+                                    n,
+                                    Msg::Batch(batch),
+                                    &mut stats[idx],
+                                );
+                                assert!(sent, "worker disconnected");
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        read_result = Err(err);
+                        break;
+                    }
+                }
+            }
+            //flush partial batches
+            for (idx, batch) in batches.into_iter().enumerate() {
+                if !batch.is_empty() {
+                    let n = batch.len();
+                    let sent = send(&channels_sender[idx], n, Msg::Batch(batch), &mut stats[idx]);
+                    assert!(sent, "worker disconnected");
+                }
+            }
+            for i in &channels_sender {
+                i.send(Msg::End).unwrap();
+            }
+
+            let finished: Vec<_> = handles.into_iter().map(|x| x.join().unwrap()).collect();
+            (read_result.map(|_| stats), finished)
+        })
+        .unwrap();
+        //sync point
+
+        let stats = read_result?;
+        Ok(merge(finished, stats, vec![], vec![]))
+    }
+
+    ///process csv from any reader, such as stdin or a pipe, parsing on the caller's thread
+    ///
+    ///rows skipped in lenient mode are returned in `skipped`
+    pub fn run_reader<R: Read>(
+        &self,
+        rdr: R,
+        mode: IngestMode,
+    ) -> Result<ParallelOutput, RowError> {
+        self.run_reader_from(Executor::default(), rdr, mode)
+    }
+
+    ///as run_reader, continuing from the state of an executor
+    pub fn run_reader_from<R: Read>(
+        &self,
+        state: Executor,
+        rdr: R,
+        mode: IngestMode,
+    ) -> Result<ParallelOutput, RowError> {
+        let mut reader = InputReader::from_reader(rdr, mode).map_err(|error| RowError {
+            row: 1,
+            error,
+            amount_error: None,
+        })?;
+        let mut finished = self.try_run_from(state, reader.by_ref())?;
+        finished.skipped = reader.take_skipped();
+        finished.rejections.extend(reader.take_rejected());
+        finished.rejections.sort_by_key(|x| x.row);
+        Ok(finished)
+    }
+
+    /// Parse a csv file on several threads and process it
+    ///
+    /// The file is split into byte ranges at line boundaries, so rows must not
+    /// contain quoted line breaks. Parser thread p takes ranges p, p + parsers,
+    /// ... and every worker consumes the ranges strictly in file order, which
+    /// keeps each client's inputs in their original order.
+    ///
+    /// Rows skipped in lenient mode are returned in `skipped`. In strict mode
+    /// the first bad row of the file is returned as the error. Errors opening
+    /// the file are reported at row 0.
+    ///
+    /// Compressed files can't be split and are parsed on one thread as in
+    /// `run_reader`.
+    pub fn run_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mode: IngestMode,
+    ) -> Result<ParallelOutput, RowError> {
+        self.run_file_from(Executor::default(), path, mode)
+    }
+
+    ///as run_file, continuing from the state of an executor
+    pub fn run_file_from<P: AsRef<Path>>(
+        &self,
+        state: Executor,
+        path: P,
+        mode: IngestMode,
+    ) -> Result<ParallelOutput, RowError> {
+        let path = path.as_ref();
+        let row_error = |row, error| RowError {
+            row,
+            error,
+            amount_error: None,
+        };
+        let compression = Compression::of_file(path).map_err(|e| row_error(0, e.into()))?;
+        if compression != Compression::None {
+            let rdr = open_input(path).map_err(|e| row_error(0, e.into()))?;
+            return self.run_reader_from(state, rdr, mode);
+        }
+
+        let mut header_reader = reader_builder()
+            .from_path(path)
+            .map_err(|e| row_error(0, e))?;
+        let headers = header_reader
+            .headers()
+            .map_err(|e| row_error(1, e))?
+            .clone();
+        let start = header_reader.position().byte();
+        let header_lines = header_reader.position().line() - 1;
+        drop(header_reader);
+        let len = fs::metadata(path)
+            .map_err(|e| row_error(0, e.into()))?
+            .len();
+
+        let num_workers = self.num_workers;
+        let num_parsers = self.parser_threads;
+        let chunk_size = self.chunk_size;
+        let num_chunks = (len.saturating_sub(start)).div_ceil(chunk_size) as usize;
+
+        //senders[parser][worker] and receivers[worker][parser]
+        let mut senders: Vec<Vec<Sender<ChunkMsg>>> = (0..num_parsers).map(|_| vec![]).collect();
+        let mut receivers: Vec<Vec<Receiver<ChunkMsg>>> =
+            (0..num_workers).map(|_| vec![]).collect();
+        for worker_receivers in receivers.iter_mut() {
+            for parser_senders in senders.iter_mut() {
+                let (sender, receiver) = bounded(CHUNKS_IN_FLIGHT);
+                parser_senders.push(sender);
+                worker_receivers.push(receiver);
+            }
+        }
+
+        //first chunk with a bad row in strict mode, or that couldn't be read
+        let failed = AtomicUsize::new(usize::MAX);
+
+        let (finished, parsed) = thread::scope(|s| {
+            let workers: Vec<_> = receivers
+                .into_iter()
+                .zip(state.split(num_workers))
+                .zip(links(num_workers))
+                .map(|((receivers, executor), links)| {
+                    s.spawn(move |_| {
+                        let mut shard = Shard::new(executor, links);
+                        let mut lines_before = header_lines;
+                        for c in 0..num_chunks {
+                            //parsers only hang up early after a failure, the output is discarded then
+                            let msg = match receivers[c % num_parsers].recv() {
+                                Ok(x) => x,
+                                Err(_) => break,
+                            };
+                            for (line, input) in msg.inputs {
+                                shard.process(lines_before + line, input);
+                            }
+                            lines_before += msg.lines;
+                        }
+                        shard.finish()
+                    })
+                })
+                .collect();
+
+            let parsers: Vec<_> = senders
+                .into_iter()
+                .enumerate()
+                .map(|(p, senders)| {
+                    let headers = &headers;
+                    let failed = &failed;
+                    s.spawn(move |_| {
+                        let mut parsed = Parsed {
+                            stats: vec![ShardStats::default(); num_workers],
+                            ..Default::default()
+                        };
+                        let mut file = match File::open(path) {
+                            Ok(x) => x,
+                            Err(e) => {
+                                failed.fetch_min(p, Ordering::SeqCst);
+                                parsed.error = Some((p, row_error(0, e.into())));
+                                return parsed;
+                            }
+                        };
+                        for c in (p..num_chunks).step_by(num_parsers) {
+                            if c > failed.load(Ordering::SeqCst) {
+                                break;
+                            }
+                            let begin = start + c as u64 * chunk_size;
+                            let buf = match read_chunk(&mut file, begin, chunk_size, start, len) {
+                                Ok(x) => x,
+                                Err(e) => {
+                                    failed.fetch_min(c, Ordering::SeqCst);
+                                    parsed.error = Some((c, row_error(0, e.into())));
+                                    break;
+                                }
+                            };
+
+                            let mut batches: Vec<Vec<(u64, Input)>> = vec![vec![]; num_workers];
+                            let mut reader =
+                                InputReader::headerless(&buf[..], headers.clone(), mode);
+                            let mut bad = None;
+                            for result in reader.by_ref() {
+                                match result {
+                                    Ok((line, input)) => {
+                                        //client must be mapped to a same worker in order for result to be correct
+                                        let (idx, peer) = workers_of(&input, num_workers);
+                                        for idx in std::iter::once(idx).chain(peer) {
+                                            batches[idx].push((line, input));
+                                        }
+                                    }
+                                    Err(e) => {
+                                        bad = Some(e);
+                                        break;
+                                    }
+                                }
+                            }
+                            parsed
+                                .skipped
+                                .extend(reader.take_skipped().into_iter().map(|x| (c, x)));
+                            parsed
+                                .rejected
+                                .extend(reader.take_rejected().into_iter().map(|x| (c, x)));
+                            if let Some(e) = bad {
+                                failed.fetch_min(c, Ordering::SeqCst);
+                                parsed.error = Some((c, e));
+                                break;
+                            }
+
+                            let lines = buf.iter().filter(|&&b| b == b'\n').count() as u64;
+                            parsed.lines.push((c, lines));
+                            for (idx, inputs) in batches.into_iter().enumerate() {
+                                let n = inputs.len();
+                                let msg = ChunkMsg { lines, inputs };
+                                if !send(&senders[idx], n, msg, &mut parsed.stats[idx]) {
+                                    //worker stopped after a failure
+                                    return parsed;
+                                }
+                            }
+                        }
+                        parsed
+                    })
+                })
+                .collect();
+
+            let finished: Vec<_> = workers.into_iter().map(|x| x.join().unwrap()).collect();
+            let parsed: Vec<_> = parsers.into_iter().map(|x| x.join().unwrap()).collect();
+            (finished, parsed)
+        })
+        .unwrap();
+        //sync point
+
+        //lines before each chunk, every chunk before a failed one has been parsed
+        let mut chunk_lines = vec![0; num_chunks];
+        let mut stats = vec![ShardStats::default(); num_workers];
+        let mut skipped = vec![];
+        let mut rejected = vec![];
+        let mut error: Option<(usize, RowError)> = None;
+        for p in parsed {
+            for (c, lines) in p.lines {
+                chunk_lines[c] = lines;
+            }
+            for (total, x) in stats.iter_mut().zip(p.stats) {
+                total.inputs += x.inputs;
+                total.batches += x.batches;
+                total.stalls += x.stalls;
+                total.stalled += x.stalled;
+            }
+            skipped.extend(p.skipped);
+            rejected.extend(p.rejected);
+            if let Some((c, e)) = p.error {
+                if error.as_ref().is_none_or(|(first, _)| c < *first) {
+                    error = Some((c, e));
+                }
+            }
+        }
+        let mut lines_before = Vec::with_capacity(num_chunks);
+        let mut sum = header_lines;
+        for lines in chunk_lines {
+            lines_before.push(sum);
+            sum += lines;
+        }
+
+        if let Some((c, mut e)) = error {
+            e.row += lines_before.get(c).copied().unwrap_or(header_lines);
+            return Err(e);
+        }
+        let mut skipped: Vec<RowError> = skipped
+            .into_iter()
+            .map(|(c, mut e)| {
+                e.row += lines_before[c];
+                e
+            })
+            .collect();
+        skipped.sort_by_key(|x| x.row);
+        let rejected = rejected.into_iter().map(|(c, mut x)| {
+            x.row += lines_before[c];
+            x
+        });
+        Ok(merge(finished, stats, skipped, rejected))
+    }
+}
+
+///combine the shards of a finished run with the rows rejected as they were read
+fn merge<I: IntoIterator<Item = RejectedInput>>(
+    shards: Vec<Shard>,
+    stats: Vec<ShardStats>,
+    skipped: Vec<RowError>,
+    rejected: I,
+) -> ParallelOutput {
+    let mut merged = ParallelOutput {
+        stats,
+        skipped,
+        rejections: rejected.into_iter().collect(),
+        ..Default::default()
+    };
+    for mut shard in shards {
+        merged.outputs.extend(shard.executor.output());
+        merged.rejections.extend(shard.rejected);
+        merged.executors.push(shard.executor);
+    }
+    merged.rejections.sort_by_key(|x| x.row);
+    merged
+}
+
+///blocking send of a batch of inputs that records how long the queue was full
+///
+///returns false if the worker has gone away
+fn send<T>(sender: &Sender<T>, inputs: usize, msg: T, stats: &mut ShardStats) -> bool {
+    stats.inputs += inputs as u64;
+    stats.batches += 1;
+    match sender.try_send(msg) {
+        Ok(()) => true,
+        Err(TrySendError::Full(msg)) => {
+            let start = Instant::now();
+            let sent = sender.send(msg).is_ok();
+            stats.stalls += 1;
+            stats.stalled += start.elapsed();
+            sent
+        }
+        Err(TrySendError::Disconnected(_)) => false,
+    }
+}
+
+///read the lines of the chunk nominally starting at begin, within the data range start..len
+fn read_chunk(
+    file: &mut File,
+    begin: u64,
+    chunk_size: u64,
+    start: u64,
+    len: u64,
+) -> io::Result<Vec<u8>> {
+    let begin_line = line_start(file, begin, start, len)?;
+    let end_line = line_start(file, begin + chunk_size, start, len)?;
+    let mut buf = Vec::with_capacity(end_line.saturating_sub(begin_line) as usize);
+    if end_line > begin_line {
+        file.seek(SeekFrom::Start(begin_line))?;
+        file.take(end_line - begin_line).read_to_end(&mut buf)?;
+    }
+    Ok(buf)
+}
+
+///byte offset of the first line starting at or after x, within the data range start..len
+fn line_start(file: &mut File, x: u64, start: u64, len: u64) -> io::Result<u64> {
+    if x <= start {
+        return Ok(start);
+    }
+    if x >= len {
+        return Ok(len);
+    }
+    //x itself starts a line if the byte before it ends one
+    file.seek(SeekFrom::Start(x - 1))?;
+    let mut line = vec![];
+    let n = BufReader::new(file).read_until(b'\n', &mut line)?;
+    if line.last() == Some(&b'\n') {
+        Ok(x - 1 + n as u64)
+    } else {
+        Ok(len)
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use crate::core::*;
+use crate::executor::*;
+
+///kind of transaction a dispute, resolve or chargeback targets
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Disputed {
+    Deposit,
+    Withdrawl,
+}
+
+///signed changes to available, held and total
+pub type Deltas = (Amount, Amount, Amount);
+
+/// How disputes move funds
+///
+/// Each step of a dispute returns the balance changes to apply for the
+/// disputed amount, or a rejection to leave the account untouched. The
+/// executor handles the dispute status and locks the account on chargeback.
+pub trait DisputePolicy: fmt::Debug + Send + Sync {
+    fn dispute(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection>;
+    fn resolve(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection>;
+    fn chargeback(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection>;
+
+    ///whether pending disputes may take held below zero, for invariant checks
+    fn negative_held(&self) -> bool {
+        false
+    }
+}
+
+fn neg(amount: Amount) -> Result<Amount, Rejection> {
+    amount.checked_neg().ok_or(Rejection::Overflow)
+}
+
+///deposit disputes hold the deposited funds, resolve releases them and chargeback removes them
+fn deposit_deltas(step: Step, amount: Amount) -> Result<Deltas, Rejection> {
+    Ok(match step {
+        Step::Dispute => (neg(amount)?, amount, Amount::ZERO),
+        Step::Resolve => (amount, neg(amount)?, Amount::ZERO),
+        Step::Chargeback => (Amount::ZERO, neg(amount)?, neg(amount)?),
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    Dispute,
+    Resolve,
+    Chargeback,
+}
+
+/// Original behavior, kept for compatibility
+///
+/// A withdrawl dispute credits available and debits held, so held goes
+/// negative while it is pending. Chargeback puts the amount back on total.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LegacyDisputes;
+
+impl LegacyDisputes {
+    fn deltas(step: Step, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        match kind {
+            Disputed::Deposit => deposit_deltas(step, amount),
+            Disputed::Withdrawl => Ok(match step {
+                Step::Dispute => (amount, neg(amount)?, Amount::ZERO),
+                Step::Resolve => (neg(amount)?, amount, Amount::ZERO),
+                Step::Chargeback => (Amount::ZERO, amount, amount),
+            }),
+        }
+    }
+}
+
+impl DisputePolicy for LegacyDisputes {
+    fn dispute(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Dispute, kind, amount)
+    }
+    fn resolve(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Resolve, kind, amount)
+    }
+    fn chargeback(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Chargeback, kind, amount)
+    }
+    fn negative_held(&self) -> bool {
+        true
+    }
+}
+
+///only deposits can be disputed, withdrawl disputes are rejected as not disputable
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DepositsOnly;
+
+impl DepositsOnly {
+    fn deltas(step: Step, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        match kind {
+            Disputed::Deposit => deposit_deltas(step, amount),
+            Disputed::Withdrawl => Err(Rejection::NotDisputable),
+        }
+    }
+}
+
+impl DisputePolicy for DepositsOnly {
+    fn dispute(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Dispute, kind, amount)
+    }
+    fn resolve(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Resolve, kind, amount)
+    }
+    fn chargeback(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Chargeback, kind, amount)
+    }
+}
+
+/// Withdrawl disputes credit the withdrawn amount to held
+///
+/// While pending the funds are held for the client, resolve drops them as the
+/// withdrawl stands and chargeback releases them to available.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WithdrawalToHeld;
+
+impl WithdrawalToHeld {
+    fn deltas(step: Step, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        match kind {
+            Disputed::Deposit => deposit_deltas(step, amount),
+            Disputed::Withdrawl => Ok(match step {
+                Step::Dispute => (Amount::ZERO, amount, amount),
+                Step::Resolve => (Amount::ZERO, neg(amount)?, neg(amount)?),
+                Step::Chargeback => (amount, neg(amount)?, Amount::ZERO),
+            }),
+        }
+    }
+}
+
+impl DisputePolicy for WithdrawalToHeld {
+    fn dispute(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Dispute, kind, amount)
+    }
+    fn resolve(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Resolve, kind, amount)
+    }
+    fn chargeback(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+        Self::deltas(Step::Chargeback, kind, amount)
+    }
+}
+
+///what to do when a dispute step would take available below zero
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum NegativeBalancePolicy {
+    #[default]
+    Allow, //apply it anyway, the original behavior
+    Reject,      //reject the step as insufficient funds
+    PartialHold, //hold only what is available and flag the account, the rest is owed
+}
+
+///how long after a deposit or withdrawl it can still be disputed
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DisputeWindow {
+    Inputs(u64),  //positions after the tx, row numbers for csv input
+    Seconds(u64), //seconds after the tx by the timestamp column
+}
+
+/// Limits on disputing a tx
+///
+/// A window is measured from when the tx was applied, which is only kept while
+/// a window is set, so txs applied before that are not limited by it. With a
+/// window in seconds a dispute without a timestamp is outside it unless the tx
+/// has none either.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct DisputeLimits {
+    pub max_disputes: Option<u32>, //disputes of a tx, counting those resolved
+    pub window: Option<DisputeWindow>,
+}
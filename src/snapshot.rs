@@ -0,0 +1,480 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::core::*;
+use crate::executor::*;
+use crate::ingest::*;
+
+const MAGIC: &[u8; 8] = b"TXSNAPSH";
+const VERSION: u16 = 13;
+
+///snapshot that could not be restored
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    BadMagic,                //not a snapshot
+    UnsupportedVersion(u16), //written by a newer or unknown format
+    Corrupt(&'static str),   //inconsistent content
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot io: {}", e),
+            SnapshotError::BadMagic => f.write_str("not an executor snapshot"),
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(f, "unsupported snapshot version {}", v)
+            }
+            SnapshotError::Corrupt(what) => write!(f, "corrupt snapshot: {}", what),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+/// Binary snapshot of an executor's state
+///
+/// Little endian, after the magic and a u16 version:
+/// - client count, then client u16, available, held and total as i64 and flagged u8
+/// - named currency balance count, then client u16, currency as 8 ascii bytes,
+///   available, held and total as i64 and flagged u8
+/// - locked client count, then each client u16
+/// - tx index count, then client u16, tx count and each tx u32
+/// - record count, then tx u32, kind u8 (0 deposit, 1 withdrawl), client u16,
+///   amount i64, dispute status u8 (0 eligible, 1 pending, 2 complete)
+/// - named currency tx count, then each client u16 and tx u32 with its currency
+/// - the last write-ahead log entry applied and the position of the last input as u64
+/// - checkpoint flag u8, then if set its byte, line and record as u64
+/// - partial hold count, then each client u16 and tx u32 with its shortfall i64
+/// - disputed tx count, then each client u16 and tx u32 with its dispute count u32
+/// - origin count, then each client u16 and tx u32, position u64, timestamp flag u8
+///   and if set the timestamp u64
+/// - disputed amounts count, then each client u16 and tx u32 with held and charged
+///   back as i64
+/// - audit entry count, then each client u16, tx u32, action u8 (0 chargeback
+///   lock, 1 admin lock, 2 admin unlock) and position u64 in order
+/// - history flag u8, then if set tx count and each client u16 and tx u32 with its
+///   transition count and each dispute status u8 and position u64 in order
+///
+/// Counts are u64 and entries are sorted so equal states give equal bytes.
+/// Only snapshots of the current version are restored.
+impl Executor {
+    ///write the full state to w
+    pub fn snapshot<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let (client_data, client_record, record, shortfalls) = self.parts();
+        let (ledgers, currencies) = self.currency_parts();
+        let (counts, origins, disputed) = self.disputes();
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+
+        let mut clients: Vec<_> = client_data.iter().collect();
+        clients.sort_by_key(|(client, _)| client.0);
+        w.write_all(&(clients.len() as u64).to_le_bytes())?;
+        for (client, data) in clients {
+            w.write_all(&client.0.to_le_bytes())?;
+            write_balances(&mut w, data)?;
+        }
+        let mut ledgers: Vec<_> = ledgers.iter().collect();
+        ledgers.sort_by_key(|((client, currency), _)| (client.0, *currency));
+        w.write_all(&(ledgers.len() as u64).to_le_bytes())?;
+        for ((client, currency), data) in ledgers {
+            w.write_all(&client.0.to_le_bytes())?;
+            w.write_all(currency.as_bytes())?;
+            write_balances(&mut w, data)?;
+        }
+        let mut locks: Vec<u16> = self.locks().iter().map(|x| x.0).collect();
+        locks.sort_unstable();
+        w.write_all(&(locks.len() as u64).to_le_bytes())?;
+        for client in locks {
+            w.write_all(&client.to_le_bytes())?;
+        }
+
+        let mut index: Vec<_> = client_record.iter().collect();
+        index.sort_by_key(|(client, _)| client.0);
+        w.write_all(&(index.len() as u64).to_le_bytes())?;
+        for (client, txs) in index {
+            let mut txs: Vec<u32> = txs.iter().map(|x| x.0).collect();
+            txs.sort_unstable();
+            w.write_all(&client.0.to_le_bytes())?;
+            w.write_all(&(txs.len() as u64).to_le_bytes())?;
+            for tx in txs {
+                w.write_all(&tx.to_le_bytes())?;
+            }
+        }
+
+        let mut records: Vec<_> = record.iter().collect();
+        records.sort_by_key(|((client, tx), _)| (client.0, tx.0));
+        w.write_all(&(records.len() as u64).to_le_bytes())?;
+        for ((client, tx), x) in records {
+            let (kind, amount, status) = match x {
+                InputInternal::Deposit(_, _, amount, status) => (0u8, amount, status),
+                InputInternal::Withdrawl(_, _, amount, status) => (1, amount, status),
+                //only deposits and withdrawls are recorded
+                _ => unreachable!("record of a non-transaction input"),
+            };
+            w.write_all(&tx.0.to_le_bytes())?;
+            w.write_all(&[kind])?;
+            w.write_all(&client.0.to_le_bytes())?;
+            w.write_all(&amount.0.to_le_bytes())?;
+            w.write_all(&[status_byte(*status)])?;
+        }
+        let mut currencies: Vec<_> = currencies.iter().collect();
+        currencies.sort_by_key(|((client, tx), _)| (client.0, tx.0));
+        w.write_all(&(currencies.len() as u64).to_le_bytes())?;
+        for (key, currency) in currencies {
+            write_key(&mut w, key)?;
+            w.write_all(currency.as_bytes())?;
+        }
+
+        w.write_all(&self.wal_seq().to_le_bytes())?;
+        w.write_all(&self.position().to_le_bytes())?;
+        match self.checkpoint() {
+            Some(x) => {
+                w.write_all(&[1])?;
+                w.write_all(&x.byte.to_le_bytes())?;
+                w.write_all(&x.line.to_le_bytes())?;
+                w.write_all(&x.record.to_le_bytes())?;
+            }
+            None => w.write_all(&[0])?,
+        }
+
+        let mut shortfalls: Vec<_> = shortfalls.iter().collect();
+        shortfalls.sort_by_key(|((client, tx), _)| (client.0, tx.0));
+        w.write_all(&(shortfalls.len() as u64).to_le_bytes())?;
+        for (key, short) in shortfalls {
+            write_key(&mut w, key)?;
+            w.write_all(&short.0.to_le_bytes())?;
+        }
+        let mut counts: Vec<_> = counts.iter().collect();
+        counts.sort_by_key(|((client, tx), _)| (client.0, tx.0));
+        w.write_all(&(counts.len() as u64).to_le_bytes())?;
+        for (key, n) in counts {
+            write_key(&mut w, key)?;
+            w.write_all(&n.to_le_bytes())?;
+        }
+        let mut origins: Vec<_> = origins.iter().collect();
+        origins.sort_by_key(|((client, tx), _)| (client.0, tx.0));
+        w.write_all(&(origins.len() as u64).to_le_bytes())?;
+        for (key, at) in origins {
+            write_key(&mut w, key)?;
+            w.write_all(&at.position.to_le_bytes())?;
+            match at.timestamp {
+                Some(x) => {
+                    w.write_all(&[1])?;
+                    w.write_all(&x.to_le_bytes())?;
+                }
+                None => w.write_all(&[0])?,
+            }
+        }
+        let mut disputed: Vec<_> = disputed.iter().collect();
+        disputed.sort_by_key(|((client, tx), _)| (client.0, tx.0));
+        w.write_all(&(disputed.len() as u64).to_le_bytes())?;
+        for (key, x) in disputed {
+            write_key(&mut w, key)?;
+            w.write_all(&x.held.0.to_le_bytes())?;
+            w.write_all(&x.charged_back.0.to_le_bytes())?;
+        }
+
+        w.write_all(&(self.audit().len() as u64).to_le_bytes())?;
+        for x in self.audit() {
+            let action = match x.action {
+                AuditAction::ChargebackLock => 0u8,
+                AuditAction::AdminLock => 1,
+                AuditAction::AdminUnlock => 2,
+            };
+            w.write_all(&x.client.0.to_le_bytes())?;
+            w.write_all(&x.tx.0.to_le_bytes())?;
+            w.write_all(&[action])?;
+            w.write_all(&x.position.to_le_bytes())?;
+        }
+
+        if self.tx_history_kept() {
+            w.write_all(&[1])?;
+            let history = self.tx_histories();
+            let txs = history.chunk_by(|a, b| (a.client, a.tx) == (b.client, b.tx));
+            w.write_all(&(txs.clone().count() as u64).to_le_bytes())?;
+            for x in txs {
+                write_key(&mut w, &(x[0].client, x[0].tx))?;
+                w.write_all(&(x.len() as u64).to_le_bytes())?;
+                for i in x {
+                    w.write_all(&[status_byte(i.status)])?;
+                    w.write_all(&i.position.to_le_bytes())?;
+                }
+            }
+        } else {
+            w.write_all(&[0])?;
+        }
+        w.flush()
+    }
+
+    ///rebuild an executor from a snapshot
+    pub fn restore<R: Read>(mut r: R) -> Result<Executor, SnapshotError> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u16::from_le_bytes(read(&mut r)?);
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut executor = Executor::default();
+        let (client_data, ..) = executor.parts_mut();
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let client = Client(u16::from_le_bytes(read(&mut r)?));
+            if client_data.insert(client, read_balances(&mut r)?).is_some() {
+                return Err(SnapshotError::Corrupt("duplicate client"));
+            }
+        }
+        let (ledgers, _) = executor.currency_parts_mut();
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let client = Client(u16::from_le_bytes(read(&mut r)?));
+            let currency = read_currency(&mut r)?;
+            if ledgers
+                .insert((client, currency), read_balances(&mut r)?)
+                .is_some()
+            {
+                return Err(SnapshotError::Corrupt("duplicate balance"));
+            }
+        }
+        let locks = executor.locks_mut();
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            if !locks.insert(Client(u16::from_le_bytes(read(&mut r)?))) {
+                return Err(SnapshotError::Corrupt("duplicate lock"));
+            }
+        }
+
+        let (_, client_record, record, _) = executor.parts_mut();
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let client = Client(u16::from_le_bytes(read(&mut r)?));
+            let txs = client_record.entry(client).or_default();
+            for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+                txs.insert(Tx(u32::from_le_bytes(read(&mut r)?)));
+            }
+        }
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let tx = Tx(u32::from_le_bytes(read(&mut r)?));
+            let kind = read::<1, _>(&mut r)?[0];
+            let client = Client(u16::from_le_bytes(read(&mut r)?));
+            let amount = Amount(i64::from_le_bytes(read(&mut r)?));
+            let status = read_status(&mut r)?;
+            let x = match kind {
+                0 => InputInternal::Deposit(client, tx, amount, status),
+                1 => InputInternal::Withdrawl(client, tx, amount, status),
+                _ => return Err(SnapshotError::Corrupt("record kind")),
+            };
+            if !client_record.get(&client).is_some_and(|x| x.contains(&tx)) {
+                return Err(SnapshotError::Corrupt("record missing from tx index"));
+            }
+            if record.insert((client, tx), x).is_some() {
+                return Err(SnapshotError::Corrupt("duplicate record"));
+            }
+        }
+        let (_, currencies) = executor.currency_parts_mut();
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let key = read_key(&mut r)?;
+            currencies.insert(key, read_currency(&mut r)?);
+        }
+
+        executor.set_wal_seq(u64::from_le_bytes(read(&mut r)?));
+        executor.set_position(u64::from_le_bytes(read(&mut r)?));
+        let checkpoint = match read::<1, _>(&mut r)?[0] {
+            0 => None,
+            1 => Some(Checkpoint {
+                byte: u64::from_le_bytes(read(&mut r)?),
+                line: u64::from_le_bytes(read(&mut r)?),
+                record: u64::from_le_bytes(read(&mut r)?),
+            }),
+            _ => return Err(SnapshotError::Corrupt("checkpoint flag")),
+        };
+        executor.set_checkpoint(checkpoint);
+
+        let (_, _, _, shortfalls) = executor.parts_mut();
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let key = read_key(&mut r)?;
+            shortfalls.insert(key, Amount(i64::from_le_bytes(read(&mut r)?)));
+        }
+        let (counts, origins, disputed) = executor.disputes_mut();
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let key = read_key(&mut r)?;
+            counts.insert(key, u32::from_le_bytes(read(&mut r)?));
+        }
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let key = read_key(&mut r)?;
+            let position = u64::from_le_bytes(read(&mut r)?);
+            let timestamp = match read::<1, _>(&mut r)?[0] {
+                0 => None,
+                1 => Some(u64::from_le_bytes(read(&mut r)?)),
+                _ => return Err(SnapshotError::Corrupt("timestamp flag")),
+            };
+            origins.insert(
+                key,
+                InputAt {
+                    position,
+                    timestamp,
+                },
+            );
+        }
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let key = read_key(&mut r)?;
+            let x = DisputedAmounts {
+                held: Amount(i64::from_le_bytes(read(&mut r)?)),
+                charged_back: Amount(i64::from_le_bytes(read(&mut r)?)),
+            };
+            disputed.insert(key, x);
+        }
+
+        for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+            let client = Client(u16::from_le_bytes(read(&mut r)?));
+            let tx = Tx(u32::from_le_bytes(read(&mut r)?));
+            let action = match read::<1, _>(&mut r)?[0] {
+                0 => AuditAction::ChargebackLock,
+                1 => AuditAction::AdminLock,
+                2 => AuditAction::AdminUnlock,
+                _ => return Err(SnapshotError::Corrupt("audit action")),
+            };
+            let position = u64::from_le_bytes(read(&mut r)?);
+            executor.audit_mut().push(AuditEntry {
+                client,
+                tx,
+                action,
+                position,
+            });
+        }
+
+        match read::<1, _>(&mut r)?[0] {
+            0 => {}
+            1 => {
+                let mut history = History::default();
+                for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+                    let (client, tx) = read_key(&mut r)?;
+                    let mut transitions = vec![];
+                    for _ in 0..u64::from_le_bytes(read(&mut r)?) {
+                        let status = read_status(&mut r)?;
+                        let position = u64::from_le_bytes(read(&mut r)?);
+                        transitions.push(Transition {
+                            client,
+                            tx,
+                            status,
+                            position,
+                        });
+                    }
+                    if history.insert((client, tx), transitions).is_some() {
+                        return Err(SnapshotError::Corrupt("duplicate history"));
+                    }
+                }
+                *executor.history_mut() = Some(history);
+            }
+            _ => return Err(SnapshotError::Corrupt("history flag")),
+        }
+
+        //nothing may follow
+        if r.read(&mut [0u8])? != 0 {
+            return Err(SnapshotError::Corrupt("trailing data"));
+        }
+        check_txs(&executor)?;
+        Ok(executor)
+    }
+}
+
+///a recorded tx implies its balances exist, and what is kept per tx is of a recorded one,
+///the executor relies on both
+fn check_txs(executor: &Executor) -> Result<(), SnapshotError> {
+    let (client_data, _, record, shortfalls) = executor.parts();
+    let (ledgers, currencies) = executor.currency_parts();
+    let (counts, origins, disputed) = executor.disputes();
+    for (client, tx) in record.keys() {
+        let known = match currencies.get(&(*client, *tx)) {
+            Some(currency) => ledgers.contains_key(&(*client, *currency)),
+            None => client_data.contains_key(client),
+        };
+        if !known {
+            return Err(SnapshotError::Corrupt("record of unknown client"));
+        }
+    }
+    let history = executor.tx_histories();
+    let mut kept = currencies
+        .keys()
+        .chain(shortfalls.keys())
+        .chain(counts.keys())
+        .chain(origins.keys())
+        .chain(disputed.keys())
+        .copied()
+        .chain(history.iter().map(|x| (x.client, x.tx)));
+    if !kept.all(|key| record.contains_key(&key)) {
+        return Err(SnapshotError::Corrupt("state of unknown tx"));
+    }
+    Ok(())
+}
+
+fn write_balances<W: Write>(w: &mut W, data: &ClientData) -> io::Result<()> {
+    w.write_all(&data.avai.0.to_le_bytes())?;
+    w.write_all(&data.held.0.to_le_bytes())?;
+    w.write_all(&data.total.0.to_le_bytes())?;
+    w.write_all(&[data.flagged as u8])
+}
+
+fn read_balances<R: Read>(r: &mut R) -> Result<ClientData, SnapshotError> {
+    Ok(ClientData {
+        avai: Amount(i64::from_le_bytes(read(r)?)),
+        held: Amount(i64::from_le_bytes(read(r)?)),
+        total: Amount(i64::from_le_bytes(read(r)?)),
+        flagged: match read::<1, _>(r)?[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(SnapshotError::Corrupt("flag")),
+        },
+    })
+}
+
+fn write_key<W: Write>(w: &mut W, (client, tx): &TxKey) -> io::Result<()> {
+    w.write_all(&client.0.to_le_bytes())?;
+    w.write_all(&tx.0.to_le_bytes())
+}
+
+fn read_key<R: Read>(r: &mut R) -> Result<TxKey, SnapshotError> {
+    let client = Client(u16::from_le_bytes(read(r)?));
+    Ok((client, Tx(u32::from_le_bytes(read(r)?))))
+}
+
+fn read_currency<R: Read>(r: &mut R) -> Result<Currency, SnapshotError> {
+    Currency::try_from(read::<8, _>(r)?).map_err(|_| SnapshotError::Corrupt("currency"))
+}
+
+fn status_byte(status: DisputeStatus) -> u8 {
+    match status {
+        DisputeStatus::Eligible => 0,
+        DisputeStatus::Pending => 1,
+        DisputeStatus::Complete => 2,
+    }
+}
+
+fn read_status<R: Read>(r: &mut R) -> Result<DisputeStatus, SnapshotError> {
+    match read::<1, _>(r)?[0] {
+        0 => Ok(DisputeStatus::Eligible),
+        1 => Ok(DisputeStatus::Pending),
+        2 => Ok(DisputeStatus::Complete),
+        _ => Err(SnapshotError::Corrupt("dispute status")),
+    }
+}
+
+fn read<const N: usize, R: Read>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
@@ -0,0 +1,432 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::core::*;
+use crate::executor::*;
+
+const MAGIC: &[u8; 8] = b"TXWAL001";
+const ENTRY_LEN: usize = 31; //seq, position, kind, client, tx, amount
+const TIMED_ENTRY_LEN: usize = ENTRY_LEN + 8; //then timestamp
+const FULL_ENTRY_LEN: usize = ENTRY_LEN + 17; //then flags, timestamp and currency
+const TRANSFER_ENTRY_LEN: usize = FULL_ENTRY_LEN + 2; //then the client transferred to
+const HAS_TIMESTAMP: u8 = 1;
+const HAS_CURRENCY: u8 = 2; //only in transfer entries, others have one by their length
+const END_OF_RUN: u8 = 8; //kind of the entry marking a finished run
+
+///logged input, numbered by the log and tagged with its position in the source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WalEntry {
+    pub seq: u64,
+    pub position: u64, //row number or other position in the input source
+    pub timestamp: Option<u64>,
+    pub currency: Option<Currency>,
+    pub input: InputInternal,
+}
+
+/// Append-only write-ahead log of inputs
+///
+/// Each entry is a u32 length, the crc32 of the payload and the payload:
+/// seq u64, position u64, kind u8 (0 deposit, 1 withdrawl, 2 dispute,
+/// 3 resolve, 4 chargeback, 5 lock, 6 unlock, 7 transfer), client u16, tx u32 and amount i64,
+/// little endian. The amount is 0 for inputs without one, such as disputes of a whole tx.
+/// Inputs with a timestamp have it appended as u64. Inputs in a named currency
+/// have a flags u8 (1 if there is a timestamp), the timestamp u64 and the
+/// currency as 8 ascii bytes appended instead. Transfers always have the flags
+/// (2 if there is a currency), timestamp and currency, then the client they go
+/// to as u16. The length tells them apart.
+/// A run that finishes appends an entry of kind 8 with nothing else set, so the
+/// positions logged before it are not taken for those of an interrupted run.
+/// A crash while appending leaves a torn tail that fails the length or
+/// checksum test, readers stop there and `open` cuts it off.
+///
+/// Entries are buffered, `sync` makes them durable.
+pub struct Wal<W: Write> {
+    w: W,
+    seq: u64, //last entry written
+}
+
+impl Wal<BufWriter<File>> {
+    ///open the log at path for appending, creating it if missing
+    ///
+    ///a torn tail is cut off, new entries are numbered on from the last valid
+    ///one or from after if that is later
+    pub fn open<P: AsRef<Path>>(path: P, after: u64) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        //a crash while creating the log may leave part of the magic
+        if file.metadata()?.len() < MAGIC.len() as u64 {
+            file.set_len(0)?;
+            return Wal::new(BufWriter::new(file), after);
+        }
+
+        let mut reader = WalReader::new(BufReader::new(&mut file))?;
+        for entry in reader.by_ref() {
+            entry?;
+        }
+        let last = reader.last_seq;
+        let valid_len = reader.valid_len();
+        file.set_len(valid_len)?;
+        file.seek(SeekFrom::Start(valid_len))?;
+        Ok(Self {
+            w: BufWriter::new(file),
+            seq: last.max(after),
+        })
+    }
+
+    ///drop all entries once a snapshot covers them, numbering carries on
+    pub fn reset(&mut self) -> io::Result<()> {
+        self.w.flush()?;
+        let file = self.w.get_mut();
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        self.w.write_all(MAGIC)?;
+        self.sync()
+    }
+
+    ///flush buffered entries and wait for them to reach the disk
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.w.flush()?;
+        self.w.get_ref().sync_data()
+    }
+}
+
+impl<W: Write> Wal<W> {
+    ///start a new log on w, numbering entries after after
+    pub fn new(mut w: W, after: u64) -> io::Result<Self> {
+        w.write_all(MAGIC)?;
+        Ok(Self { w, seq: after })
+    }
+
+    ///append an input and return its sequence number
+    pub fn append(
+        &mut self,
+        at: InputAt,
+        currency: Option<Currency>,
+        input: &InputInternal,
+    ) -> io::Result<u64> {
+        let seq = self.seq + 1;
+        let mut to = None;
+        let (kind, client, tx, amount) = match *input {
+            InputInternal::Deposit(client, tx, amount, _) => (0u8, client, tx, amount),
+            InputInternal::Withdrawl(client, tx, amount, _) => (1, client, tx, amount),
+            InputInternal::Dispute(client, tx, amount) => {
+                (2, client, tx, amount.unwrap_or_default())
+            }
+            InputInternal::Resolve(client, tx) => (3, client, tx, Amount::ZERO),
+            InputInternal::Chargeback(client, tx) => (4, client, tx, Amount::ZERO),
+            InputInternal::Lock(client, tx) => (5, client, tx, Amount::ZERO),
+            InputInternal::Unlock(client, tx) => (6, client, tx, Amount::ZERO),
+            InputInternal::Transfer(client, to_, tx, amount) => {
+                to = Some(to_);
+                (7, client, tx, amount)
+            }
+        };
+        let mut payload = [0u8; TRANSFER_ENTRY_LEN];
+        payload[0..8].copy_from_slice(&seq.to_le_bytes());
+        payload[8..16].copy_from_slice(&at.position.to_le_bytes());
+        payload[16] = kind;
+        payload[17..19].copy_from_slice(&client.0.to_le_bytes());
+        payload[19..23].copy_from_slice(&tx.0.to_le_bytes());
+        payload[23..31].copy_from_slice(&amount.0.to_le_bytes());
+        let payload = match (at.timestamp, currency) {
+            (timestamp, currency) if to.is_some() => {
+                let mut flags = 0;
+                if timestamp.is_some() {
+                    flags |= HAS_TIMESTAMP;
+                }
+                if currency.is_some() {
+                    flags |= HAS_CURRENCY;
+                }
+                payload[31] = flags;
+                payload[32..40].copy_from_slice(&timestamp.unwrap_or(0).to_le_bytes());
+                payload[40..48].copy_from_slice(currency.unwrap_or_default().as_bytes());
+                payload[48..50].copy_from_slice(&to.unwrap().0.to_le_bytes());
+                &payload[..]
+            }
+            (timestamp, Some(currency)) => {
+                payload[31] = if timestamp.is_some() {
+                    HAS_TIMESTAMP
+                } else {
+                    0
+                };
+                payload[32..40].copy_from_slice(&timestamp.unwrap_or(0).to_le_bytes());
+                payload[40..48].copy_from_slice(currency.as_bytes());
+                &payload[..FULL_ENTRY_LEN]
+            }
+            (Some(x), None) => {
+                payload[31..39].copy_from_slice(&x.to_le_bytes());
+                &payload[..TIMED_ENTRY_LEN]
+            }
+            (None, None) => &payload[..ENTRY_LEN],
+        };
+
+        self.w.write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.w.write_all(&crc32fast::hash(payload).to_le_bytes())?;
+        self.w.write_all(payload)?;
+        self.seq = seq;
+        Ok(seq)
+    }
+
+    ///mark the end of a run, the inputs logged before it are not resumed from
+    pub fn finish_run(&mut self) -> io::Result<u64> {
+        let seq = self.seq + 1;
+        let mut payload = [0u8; ENTRY_LEN];
+        payload[0..8].copy_from_slice(&seq.to_le_bytes());
+        payload[16] = END_OF_RUN;
+        self.w.write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.w.write_all(&crc32fast::hash(&payload).to_le_bytes())?;
+        self.w.write_all(&payload)?;
+        self.seq = seq;
+        Ok(seq)
+    }
+
+    ///sequence number of the last entry
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// Reads entries of a write-ahead log
+///
+/// Iteration ends at the end of the log or at the first entry that is cut
+/// short, fails its checksum or is out of sequence, after which `torn` tells
+/// which it was.
+pub struct WalReader<R> {
+    r: R,
+    valid_len: u64, //bytes up to the end of the last valid entry
+    last_seq: u64,
+    torn: bool,
+    finished: bool, //the last valid entry marks the end of a run
+    done: bool,
+}
+
+impl<R: Read> WalReader<R> {
+    pub fn new(mut r: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a write-ahead log",
+            ));
+        }
+        Ok(Self {
+            r,
+            valid_len: MAGIC.len() as u64,
+            last_seq: 0,
+            torn: false,
+            finished: false,
+            done: false,
+        })
+    }
+
+    ///whether the log ends in a damaged entry
+    pub fn torn(&self) -> bool {
+        self.torn
+    }
+
+    pub fn valid_len(&self) -> u64 {
+        self.valid_len
+    }
+
+    ///whether the log read so far ends with the end of a run, see `Wal::finish_run`
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    ///read an entry, None at a clean end or a damaged entry
+    fn read_entry(&mut self) -> io::Result<Option<WalEntry>> {
+        //end of run markers only tell where runs finished
+        loop {
+            match self.read_record()? {
+                Some(Some(x)) => return Ok(Some(x)),
+                Some(None) => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    ///read an entry, Some(None) for the end of a run and None at a clean end or
+    ///a damaged entry
+    fn read_record(&mut self) -> io::Result<Option<Option<WalEntry>>> {
+        let mut header = [0u8; 8];
+        let n = read_full(&mut self.r, &mut header)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        let mut buf = [0u8; TRANSFER_ENTRY_LEN];
+        let len = match u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize {
+            x @ (ENTRY_LEN | TIMED_ENTRY_LEN | FULL_ENTRY_LEN | TRANSFER_ENTRY_LEN)
+                if n == header.len() =>
+            {
+                x
+            }
+            _ => {
+                self.torn = true;
+                return Ok(None);
+            }
+        };
+        let payload = &mut buf[..len];
+        if read_full(&mut self.r, payload)? < len
+            || u32::from_le_bytes(header[4..8].try_into().unwrap()) != crc32fast::hash(payload)
+        {
+            self.torn = true;
+            return Ok(None);
+        }
+
+        let seq = u64::from_le_bytes(payload[0..8].try_into().unwrap());
+        let position = u64::from_le_bytes(payload[8..16].try_into().unwrap());
+        let client = Client(u16::from_le_bytes(payload[17..19].try_into().unwrap()));
+        let tx = Tx(u32::from_le_bytes(payload[19..23].try_into().unwrap()));
+        let amount = Amount(i64::from_le_bytes(payload[23..31].try_into().unwrap()));
+        let (timestamp, currency) = match len {
+            TIMED_ENTRY_LEN => (
+                Some(u64::from_le_bytes(payload[31..39].try_into().unwrap())),
+                None,
+            ),
+            FULL_ENTRY_LEN | TRANSFER_ENTRY_LEN => {
+                let flags = payload[31];
+                let timestamp = u64::from_le_bytes(payload[32..40].try_into().unwrap());
+                let code: [u8; 8] = payload[40..48].try_into().unwrap();
+                let currency = if len == FULL_ENTRY_LEN || flags & HAS_CURRENCY != 0 {
+                    let Ok(currency) = Currency::try_from(code) else {
+                        self.torn = true;
+                        return Ok(None);
+                    };
+                    Some(currency)
+                } else {
+                    None
+                };
+                ((flags & HAS_TIMESTAMP != 0).then_some(timestamp), currency)
+            }
+            _ => (None, None),
+        };
+        let input = match payload[16] {
+            0 => Some(InputInternal::Deposit(
+                client,
+                tx,
+                amount,
+                DisputeStatus::Eligible,
+            )),
+            1 => Some(InputInternal::Withdrawl(
+                client,
+                tx,
+                amount,
+                DisputeStatus::Eligible,
+            )),
+            2 => Some(InputInternal::Dispute(
+                client,
+                tx,
+                Some(amount).filter(|x| *x != Amount::ZERO),
+            )),
+            3 => Some(InputInternal::Resolve(client, tx)),
+            4 => Some(InputInternal::Chargeback(client, tx)),
+            5 => Some(InputInternal::Lock(client, tx)),
+            6 => Some(InputInternal::Unlock(client, tx)),
+            7 if len == TRANSFER_ENTRY_LEN => {
+                let to = Client(u16::from_le_bytes(payload[48..50].try_into().unwrap()));
+                Some(InputInternal::Transfer(client, to, tx, amount))
+            }
+            END_OF_RUN if len == ENTRY_LEN => None,
+            _ => {
+                self.torn = true;
+                return Ok(None);
+            }
+        };
+        if seq <= self.last_seq {
+            self.torn = true;
+            return Ok(None);
+        }
+        self.last_seq = seq;
+        self.valid_len += (header.len() + len) as u64;
+        self.finished = input.is_none();
+        Ok(Some(input.map(|input| WalEntry {
+            seq,
+            position,
+            timestamp,
+            currency,
+            input,
+        })))
+    }
+}
+
+impl<R: Read> Iterator for WalReader<R> {
+    type Item = io::Result<WalEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.read_entry().transpose();
+        if !matches!(res, Some(Ok(_))) {
+            self.done = true;
+        }
+        res
+    }
+}
+
+///read until buf is full or the end of r, returning the bytes read
+fn read_full<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(x) => n += x,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+///what replaying a write-ahead log did
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Replayed {
+    pub applied: u64,               //entries applied, rejected or not
+    pub skipped: u64,               //entries already covered by the state
+    pub last_position: Option<u64>, //position of the last entry in the log
+    pub torn: bool,                 //the log ended in a damaged entry that was ignored
+    pub finished: bool,             //the log ends with a finished run, not one to resume
+}
+
+impl Executor {
+    ///apply the entries of a write-ahead log that are newer than the state
+    ///
+    ///recovery is restoring the last snapshot then replaying the log on top
+    pub fn replay<R: Read>(&mut self, r: R) -> io::Result<Replayed> {
+        let mut reader = WalReader::new(r)?;
+        let mut replayed = Replayed::default();
+        for entry in reader.by_ref() {
+            let entry = entry?;
+            replayed.last_position = Some(entry.position);
+            if entry.seq <= self.wal_seq() {
+                replayed.skipped += 1;
+                continue;
+            }
+            //rejections are reproduced as they were when logged
+            let at = InputAt {
+                position: entry.position,
+                timestamp: entry.timestamp,
+            };
+            let _ = self.apply(at, entry.currency, entry.input);
+            self.set_wal_seq(entry.seq);
+            replayed.applied += 1;
+        }
+        replayed.torn = reader.torn();
+        replayed.finished = reader.finished();
+        Ok(replayed)
+    }
+}
@@ -0,0 +1,126 @@
+//! random inputs shared by the tests comparing executors
+#![allow(dead_code)] //each test uses the settings it needs
+
+use std::ops::Range;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use transaction::*;
+
+/// Generator of random inputs, the same ones for the same seed and settings
+///
+/// Deposits, withdrawls, transfers and admin actions get the next tx id, the
+/// others name a random one seen so far, so many are rejected.
+pub struct InputGen {
+    rng: StdRng,
+    types: Vec<(InputType, u32)>, //weight of each input type
+    clients: u16,
+    amounts: Range<i64>,
+    next_tx: u32,
+    partial_disputes: f64,             //share of disputes of part of the tx
+    currency: Option<(Currency, f64)>, //currency and share of inputs in it
+}
+
+impl InputGen {
+    ///deposits, withdrawls, disputes, resolves and chargebacks for 10 clients
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            types: vec![
+                (InputType::Deposit, 2),
+                (InputType::Withdrawl, 1),
+                (InputType::Dispute, 1),
+                (InputType::Resolve, 1),
+                (InputType::Chargeback, 1),
+            ],
+            clients: 10,
+            amounts: 1..50_0000,
+            next_tx: 0,
+            partial_disputes: 0.0,
+            currency: None,
+        }
+    }
+
+    ///input types by weight
+    pub fn with_types(mut self, types: &[(InputType, u32)]) -> Self {
+        self.types = types.to_vec();
+        self
+    }
+
+    pub fn with_clients(mut self, clients: u16) -> Self {
+        self.clients = clients;
+        self
+    }
+
+    ///range of amounts of deposits, withdrawls and transfers
+    pub fn with_amounts(mut self, amounts: Range<i64>) -> Self {
+        self.amounts = amounts;
+        self
+    }
+
+    ///start tx ids after txs generated before, such as by another generator
+    pub fn with_first_tx(mut self, tx: u32) -> Self {
+        self.next_tx = tx;
+        self
+    }
+
+    ///share of disputes of part of the tx
+    pub fn with_partial_disputes(mut self, share: f64) -> Self {
+        self.partial_disputes = share;
+        self
+    }
+
+    ///share of inputs in a named currency
+    pub fn with_currency(mut self, currency: Currency, share: f64) -> Self {
+        self.currency = Some((currency, share));
+        self
+    }
+
+    pub fn input(&mut self) -> Input {
+        let total: u32 = self.types.iter().map(|x| x.1).sum();
+        let mut pick = self.rng.gen_range(0..total);
+        let mut ty = self.types[0].0;
+        for &(x, weight) in &self.types {
+            if pick < weight {
+                ty = x;
+                break;
+            }
+            pick -= weight;
+        }
+        let seen = self.next_tx.max(1);
+        let (tx, amount) = match ty {
+            InputType::Deposit | InputType::Withdrawl | InputType::Transfer => {
+                let amount = self.rng.gen_range(self.amounts.clone());
+                (self.next_tx, Some(Amount(amount)))
+            }
+            InputType::Lock | InputType::Unlock => (self.next_tx, None),
+            InputType::Dispute if self.rng.gen_bool(self.partial_disputes) => {
+                let amount = self.rng.gen_range(1..self.amounts.end.max(2));
+                (self.rng.gen_range(0..seen), Some(Amount(amount)))
+            }
+            _ => (self.rng.gen_range(0..seen), None),
+        };
+        self.next_tx += 1;
+        let client = Client(self.rng.gen_range(0..self.clients));
+        let to = match ty {
+            InputType::Transfer => Some(Client(self.rng.gen_range(0..self.clients))),
+            _ => None,
+        };
+        let currency = match self.currency {
+            Some((x, share)) if self.rng.gen_bool(share) => Some(x),
+            _ => None,
+        };
+        Input {
+            ty,
+            client,
+            tx: Tx(tx),
+            amount,
+            timestamp: None,
+            currency,
+            to,
+        }
+    }
+
+    pub fn inputs(&mut self, n: usize) -> Vec<Input> {
+        (0..n).map(|_| self.input()).collect()
+    }
+}
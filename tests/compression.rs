@@ -0,0 +1,83 @@
+#[cfg(test)]
+const DATA: &str = "type,client,tx,amount\ndeposit,1,1,2.0\nwithdrawal,1,2,0.5\ndeposit,2,3,1.0\n";
+
+#[test]
+fn compressed_files_by_extension() {
+    use std::io::{Read, Write};
+    use transaction::*;
+
+    for ext in ["csv", "csv.gz", "csv.zst"] {
+        let path = std::env::temp_dir().join(format!("compressed_{}.{}", std::process::id(), ext));
+        let mut w = create_output(&path).unwrap();
+        w.write_all(DATA.as_bytes()).unwrap();
+        w.finish().unwrap();
+
+        let mut raw = vec![];
+        std::fs::File::open(&path)
+            .unwrap()
+            .read_to_end(&mut raw)
+            .unwrap();
+        assert_eq!(raw == DATA.as_bytes(), ext == "csv");
+
+        let rows: Vec<u64> = InputReader::from_path(&path, IngestMode::Strict)
+            .unwrap()
+            .map(|x| x.unwrap().0)
+            .collect();
+        assert_eq!(rows, vec![2, 3, 4]);
+
+        let finished = ParallelExecutor::new(2)
+            .run_file(&path, IngestMode::Strict)
+            .unwrap();
+        assert_eq!(finished.outputs.len(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn compressed_streams_by_magic() {
+    use std::io::{Read, Write};
+    use transaction::*;
+
+    let zst = zstd::encode_all(DATA.as_bytes(), 0).unwrap();
+    let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+    gz.write_all(DATA.as_bytes()).unwrap();
+    let gz = gz.finish().unwrap();
+
+    assert_eq!(Compression::from_magic(&gz), Compression::Gzip);
+    assert_eq!(Compression::from_magic(&zst), Compression::Zstd);
+    assert_eq!(Compression::from_magic(DATA.as_bytes()), Compression::None);
+
+    for bytes in [DATA.as_bytes().to_vec(), gz, zst, b"ab".to_vec()] {
+        let expected = if bytes == b"ab" { "ab" } else { DATA };
+        let mut out = String::new();
+        decompressed(std::io::Cursor::new(bytes))
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, expected);
+    }
+}
+
+#[test]
+fn compressed_output_reports_unfinished_stream() {
+    use std::io::{Read, Write};
+    use transaction::*;
+
+    for compression in [Compression::Gzip, Compression::Zstd] {
+        //room for little more than a header, the trailer can't be written
+        let mut buf = [0u8; 12];
+        let mut w = compression.encoder(&mut buf[..]).unwrap();
+        w.write_all(DATA.as_bytes()).unwrap();
+        assert!(w.finish().is_err());
+
+        let mut w = compression.encoder(vec![]).unwrap();
+        w.write_all(DATA.as_bytes()).unwrap();
+        let finished = w.finish().unwrap();
+        let mut out = String::new();
+        decompressed(std::io::Cursor::new(finished))
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, DATA);
+    }
+}
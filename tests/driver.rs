@@ -0,0 +1,231 @@
+#[cfg(test)]
+fn driver(args: &[&str]) -> (String, String) {
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_driver"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{:?}", out);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    //clients come out in no particular order
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    (lines.join("\n"), String::from_utf8(out.stderr).unwrap())
+}
+
+#[cfg(test)]
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+fn write_input(dir: &std::path::Path, name: &str, rows: &[&str]) -> String {
+    let path = dir.join(name);
+    let data = format!("type,client,tx,amount\n{}\n", rows.join("\n"));
+    std::fs::write(&path, data).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn driver_recovers_from_log_without_checkpoint() {
+    use transaction::*;
+
+    let dir = temp_dir("driver_recovers");
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let rows = [
+        "deposit,1,1,10.0",
+        "dispute,1,1,",
+        "resolve,1,1,",
+        "deposit,2,2,3.0",
+        "dispute,1,1,",
+        "chargeback,1,1,",
+        "deposit,1,3,1.0",
+    ];
+    let full = write_input(&dir, "full.csv", &rows);
+    let (wal, state) = (path("wal.log"), path("state.bin"));
+    let (audit, rejections) = (path("audit.csv"), path("rejections.csv"));
+    let read = |x: &str| std::fs::read_to_string(x).unwrap();
+    let reports = [
+        "--max-disputes",
+        "2",
+        "--audit",
+        &audit,
+        "--rejections",
+        &rejections,
+    ];
+
+    let (expected, _) = driver(&[&[full.as_str()], &reports[..]].concat());
+    let outputs = [
+        "1,0.0000,0.0000,0.0000,true",
+        "2,3.0000,0.0000,3.0000,false",
+        "client,available,held,total,locked",
+    ];
+    assert_eq!(expected, outputs.join("\n"));
+    let (expected_audit, expected_rejections) = (read(&audit), read(&rejections));
+
+    //a run logs the first rows then dies before any state is saved
+    let limits = DisputeLimits {
+        max_disputes: Some(2),
+        window: None,
+    };
+    let mut executor = Executor::default().with_dispute_limits(limits);
+    let mut log = Wal::open(&wal, 0).unwrap();
+    let head = write_input(&dir, "head.csv", &rows[..4]);
+    let reader = InputReader::from_path(&head, IngestMode::Strict).unwrap();
+    for x in reader {
+        let (row, input) = x.unwrap();
+        executor
+            .process_logged(row, input, &mut log)
+            .unwrap()
+            .unwrap();
+    }
+    log.sync().unwrap();
+    drop(log);
+
+    let (recovered, stderr) = driver(
+        &[
+            &[full.as_str()],
+            &reports[..],
+            &["--wal", &wal, "--state", &state],
+        ]
+        .concat(),
+    );
+    assert!(stderr.contains("recovered 4 logged inputs"), "{}", stderr);
+    assert!(stderr.contains("resuming at line 6"), "{}", stderr);
+    assert_eq!(recovered, expected);
+    assert_eq!(read(&audit), expected_audit);
+    //logged rows are not processed again, so their deposits aren't duplicates
+    assert_eq!(read(&rejections), expected_rejections);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn driver_reads_all_rows_after_a_finished_run() {
+    use transaction::*;
+
+    let dir = temp_dir("driver_finished");
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let first = write_input(&dir, "first.csv", &["deposit,1,1,10.0", "deposit,1,2,1.0"]);
+    let second = write_input(
+        &dir,
+        "second.csv",
+        &["deposit,2,3,3.0", "deposit,2,4,2.0", "deposit,1,5,1.0"],
+    );
+    let outputs = [
+        "1,12.0000,0.0000,12.0000,false",
+        "2,5.0000,0.0000,5.0000,false",
+        "client,available,held,total,locked",
+    ];
+
+    //without a state the log is what the next run starts from
+    let wal = path("wal.log");
+    driver(&[&first, "--wal", &wal]);
+    let (out, stderr) = driver(&[&second, "--wal", &wal]);
+    assert!(stderr.contains("recovered 2 logged inputs"), "{}", stderr);
+    assert!(!stderr.contains("resuming"), "{}", stderr);
+    assert_eq!(out, outputs.join("\n"));
+
+    //a run that dies after saving its state but before emptying the log
+    let (wal, state) = (path("wal_state.log"), path("state.bin"));
+    let mut executor = Executor::default();
+    let mut log = Wal::open(&wal, 0).unwrap();
+    let reader = InputReader::from_path(&first, IngestMode::Strict).unwrap();
+    for x in reader {
+        let (row, input) = x.unwrap();
+        executor
+            .process_logged(row, input, &mut log)
+            .unwrap()
+            .unwrap();
+    }
+    log.finish_run().unwrap();
+    log.sync().unwrap();
+    executor
+        .snapshot(std::fs::File::create(&state).unwrap())
+        .unwrap();
+    drop(log);
+
+    let (out, stderr) = driver(&[&second, "--wal", &wal, "--state", &state]);
+    assert!(!stderr.contains("resuming"), "{}", stderr);
+    assert_eq!(out, outputs.join("\n"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn driver_keeps_rejections_of_resumed_run() {
+    use transaction::*;
+
+    let dir = temp_dir("driver_rejections");
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let input = write_input(
+        &dir,
+        "input.csv",
+        &[
+            "deposit,1,1,10.0",
+            "withdrawal,2,2,5.0",
+            "deposit,1,3,1.0",
+            "withdrawal,1,4,50.0",
+            "deposit,1,5,1.0",
+            "withdrawal,3,6,1.0",
+        ],
+    );
+    let (state, rejections) = (path("state.bin"), path("rejections.csv"));
+    let read = |x: &str| std::fs::read_to_string(x).unwrap();
+    driver(&[&input, "--rejections", &rejections]);
+    let expected = read(&rejections);
+    assert_eq!(expected.lines().count(), 4);
+
+    //a run saves its state after line 4 and reports line 5 before it dies
+    let mut executor = Executor::default();
+    let mut reader = InputReader::from_path(&input, IngestMode::Strict).unwrap();
+    let mut w = csv::Writer::from_path(&rejections).unwrap();
+    for _ in 0..4 {
+        let (row, input) = reader.next().unwrap().unwrap();
+        if let Err(reason) = executor.process_at(row, input) {
+            w.serialize(RejectedInput::from((row, input, reason)))
+                .unwrap();
+        }
+        if row == 4 {
+            executor.set_checkpoint(Some(reader.checkpoint()));
+            executor
+                .snapshot(std::fs::File::create(&state).unwrap())
+                .unwrap();
+        }
+    }
+    w.flush().unwrap();
+
+    let (_, stderr) = driver(&[&input, "--state", &state, "--rejections", &rejections]);
+    assert!(stderr.contains("resuming at line 5"), "{}", stderr);
+    assert_eq!(read(&rejections), expected);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn driver_reports_invalid_amounts_as_given() {
+    let dir = temp_dir("driver_amounts");
+    let rejections = dir.join("rejections.csv").to_str().unwrap().to_string();
+    let input = write_input(
+        &dir,
+        "input.csv",
+        &[
+            "deposit,1,1,NaN",
+            "deposit,1,2,10.0",
+            "withdrawal,1,3,50.0",
+            "deposit,1,4,1.00001",
+        ],
+    );
+    let (stdout, _) = driver(&[&input, "--rejections", &rejections]);
+    assert!(
+        stdout.contains("1,10.0000,0.0000,10.0000,false"),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        std::fs::read_to_string(&rejections).unwrap(),
+        "row,type,client,tx,amount,timestamp,currency,to,reason\n\
+         2,deposit,1,1,NaN,,,,non_finite_amount\n\
+         4,withdrawal,1,3,50.0000,,,,insufficient_funds\n\
+         5,deposit,1,4,1.00001,,,,too_precise_amount\n"
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
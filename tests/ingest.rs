@@ -0,0 +1,231 @@
+#[test]
+fn ingest_lenient_skips_bad_rows() {
+    use transaction::*;
+
+    let data =
+        "type,client,tx,amount\nDeposit,1,1,1.0\nDeposit,1,2,abc\nFoo,1,3,1.0\nDeposit,1,4,2.0\n";
+    let mut reader = InputReader::from_reader(data.as_bytes(), IngestMode::Lenient).unwrap();
+    let rows: Vec<_> = reader.by_ref().map(|x| x.unwrap().0).collect();
+    assert_eq!(rows, vec![2, 5]);
+
+    let skipped: Vec<_> = reader.skipped().iter().map(|x| x.row).collect();
+    assert_eq!(skipped, vec![3, 4]);
+}
+
+#[test]
+fn ingest_strict_stops_at_first_bad_row() {
+    use transaction::*;
+
+    let data = "type,client,tx,amount\nDeposit,1,1,1.0\nDeposit,1,2,abc\nDeposit,1,4,2.0\n";
+    let mut reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    assert_eq!(reader.next().unwrap().unwrap().0, 2);
+    let err = reader.next().unwrap().unwrap_err();
+    assert_eq!(err.row, 3);
+    assert!(reader.next().is_none());
+    assert!(reader.skipped().is_empty());
+}
+
+#[test]
+fn ingest_missing_amount_is_rejected() {
+    use transaction::*;
+
+    let data = "type,client,tx,amount\nDeposit,1,1,\nWithdrawl,1,2,\n";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let mut executor = Executor::default();
+    for result in reader {
+        let (_row, input) = result.unwrap();
+        assert_eq!(executor.process(input), Err(Rejection::MissingAmount));
+    }
+}
+
+#[test]
+fn ingest_tolerates_padding_case_and_spelling() {
+    use transaction::*;
+
+    let data = "type, client, tx, amount\ndeposit, 1, 1, 1.0\nDEPOSIT,1,2,2.0\n  Withdrawal , 1, 3, 0.5\nwithdrawl,1,4,0.25\nDispute, 1, 1\n";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let inputs: Vec<_> = reader.map(|x| x.unwrap().1).collect();
+    let types: Vec<_> = inputs.iter().map(|x| x.ty).collect();
+    assert_eq!(
+        types,
+        vec![
+            InputType::Deposit,
+            InputType::Deposit,
+            InputType::Withdrawl,
+            InputType::Withdrawl,
+            InputType::Dispute
+        ]
+    );
+    assert_eq!(inputs[2].amount, Some(Amount(5000)));
+    assert_eq!(inputs[4].amount, None);
+
+    //written back in one canonical form
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.serialize(inputs[2]).unwrap();
+    writer.serialize(inputs[3]).unwrap();
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "type,client,tx,amount,timestamp,currency,to\n\
+         withdrawal,1,3,0.5000,,,\n\
+         withdrawal,1,4,0.2500,,,\n"
+    );
+}
+
+#[test]
+fn ingest_writes_mixed_inputs_back() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount,timestamp,currency,to
+deposit,1,1,2.0,,,
+transfer,1,2,1.0,100,USD,2
+dispute,1,1,,,,
+";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let inputs: Vec<_> = reader.map(|x| x.unwrap().1).collect();
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for i in &inputs {
+        writer.serialize(i).unwrap();
+    }
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "type,client,tx,amount,timestamp,currency,to\n\
+         deposit,1,1,2.0000,,,\n\
+         transfer,1,2,1.0000,100,USD,2\n\
+         dispute,1,1,,,,\n"
+    );
+    let reader = InputReader::from_reader(written.as_bytes(), IngestMode::Strict).unwrap();
+    let read: Vec<_> = reader.map(|x| x.unwrap().1).collect();
+    assert_eq!(read, inputs);
+
+    //balances of the unnamed currency have an empty currency column
+    let outputs = [
+        Output {
+            client: Client(1),
+            currency: None,
+            available: Amount(1_0000),
+            held: Amount::ZERO,
+            total: Amount(1_0000),
+            locked: false,
+        },
+        Output {
+            client: Client(1),
+            currency: Some("USD".parse().unwrap()),
+            available: Amount::ZERO,
+            held: Amount::ZERO,
+            total: Amount::ZERO,
+            locked: false,
+        },
+    ];
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for i in &outputs {
+        writer.serialize(i).unwrap();
+    }
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "client,currency,available,held,total,locked\n\
+         1,,1.0000,0.0000,1.0000,false\n\
+         1,USD,0.0000,0.0000,0.0000,false\n"
+    );
+}
+
+#[test]
+fn ingest_reports_amount_errors() {
+    use transaction::*;
+
+    assert_eq!("NaN".parse::<Amount>(), Err(AmountParseError::NonFinite));
+    assert_eq!("-inf".parse::<Amount>(), Err(AmountParseError::NonFinite));
+
+    let data = "type,client,tx,amount\ndeposit,1,1,NaN\ndeposit,1,2,1.00001\ndeposit,1,3,12x\nfoo,1,4,1.0\ndeposit,1,5,1.5\n";
+    let mut reader = InputReader::from_reader(data.as_bytes(), IngestMode::Lenient).unwrap();
+    let rows: Vec<_> = reader.by_ref().map(|x| x.unwrap().0).collect();
+    assert_eq!(rows, vec![6]);
+    let errors: Vec<_> = reader
+        .skipped()
+        .iter()
+        .map(|x| (x.row, x.amount_error))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![(4, Some(AmountParseError::Invalid)), (5, None)]
+    );
+
+    //numbers that are no valid amounts are rejected with the amount as given
+    let rejected: Vec<_> = reader
+        .rejected()
+        .iter()
+        .map(|x| (x.row, x.amount.as_deref(), x.reason))
+        .collect();
+    assert_eq!(
+        rejected,
+        vec![
+            (2, Some("NaN"), Rejection::NonFiniteAmount),
+            (3, Some("1.00001"), Rejection::TooPreciseAmount),
+        ]
+    );
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for x in reader.take_rejected() {
+        writer.serialize(x).unwrap();
+    }
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "row,type,client,tx,amount,timestamp,currency,to,reason\n\
+         2,deposit,1,1,NaN,,,,non_finite_amount\n\
+         3,deposit,1,2,1.00001,,,,too_precise_amount\n"
+    );
+    assert!(reader.rejected().is_empty());
+
+    //and don't stop a strict read
+    let data = "type,client,tx,amount\ndeposit,1,1,1.23456\ndeposit,1,2,inf\ndeposit,1,3,1.5\n";
+    let mut reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let inputs: Vec<_> = reader.by_ref().map(|x| x.unwrap()).collect();
+    assert_eq!(inputs.len(), 1);
+    assert_eq!(inputs[0].0, 4);
+    let rows: Vec<_> = reader.rejected().iter().map(|x| x.row).collect();
+    assert_eq!(rows, vec![2, 3]);
+
+    //plain deserialization fails on them
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    assert!(reader.deserialize::<Input>().next().unwrap().is_err());
+}
+
+#[test]
+fn ingest_resume_from_checkpoint() {
+    use std::io::Write;
+    use transaction::*;
+
+    let mut data = String::from("type, client, tx, amount\n");
+    for i in 1..=50 {
+        data.push_str(&format!("deposit, {}, {}, {}.5\n", i % 4, i, i));
+    }
+    for ext in ["csv", "csv.gz"] {
+        let path = std::env::temp_dir().join(format!("resume_{}.{}", std::process::id(), ext));
+        let mut w = create_output(&path).unwrap();
+        w.write_all(data.as_bytes()).unwrap();
+        w.finish().unwrap();
+
+        let all: Vec<_> = InputReader::from_path(&path, IngestMode::Strict)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let mut reader = InputReader::from_path(&path, IngestMode::Strict).unwrap();
+        let first: Vec<_> = reader.by_ref().take(20).map(|x| x.unwrap()).collect();
+        let checkpoint = reader.checkpoint();
+        assert_eq!(checkpoint.line, 22);
+        assert_eq!(checkpoint.record, 21);
+
+        let rest: Vec<_> = InputReader::resume(&path, IngestMode::Strict, checkpoint)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let rows: Vec<u64> = first.iter().chain(rest.iter()).map(|x| x.0).collect();
+        let inputs: Vec<_> = first.iter().chain(rest.iter()).map(|x| x.1).collect();
+        assert_eq!(rows, all.iter().map(|x| x.0).collect::<Vec<_>>());
+        assert_eq!(inputs, all.iter().map(|x| x.1).collect::<Vec<_>>());
+        std::fs::remove_file(path).unwrap();
+    }
+}
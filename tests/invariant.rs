@@ -0,0 +1,130 @@
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+use common::InputGen;
+
+#[test]
+fn invariants_hold_under_every_policy() {
+    use transaction::*;
+
+    let negative_balances = [
+        NegativeBalancePolicy::Allow,
+        NegativeBalancePolicy::Reject,
+        NegativeBalancePolicy::PartialHold,
+    ];
+    for (seed, negative_balance) in negative_balances.into_iter().enumerate() {
+        let inputs = InputGen::new(seed as u64)
+            .with_types(&[
+                (InputType::Deposit, 2),
+                (InputType::Withdrawl, 1),
+                (InputType::Dispute, 2),
+                (InputType::Resolve, 1),
+                (InputType::Chargeback, 1),
+                (InputType::Unlock, 1),
+                (InputType::Transfer, 1),
+            ])
+            .with_clients(8)
+            .with_partial_disputes(0.3)
+            .with_currency("EUR".parse().unwrap(), 0.3)
+            .inputs(4000);
+        for policy in 0..3 {
+            let executor = || {
+                let executor = Executor::default();
+                let executor = match policy {
+                    0 => executor.with_dispute_policy(LegacyDisputes),
+                    1 => executor.with_dispute_policy(DepositsOnly),
+                    _ => executor.with_dispute_policy(WithdrawalToHeld),
+                };
+                executor
+                    .with_negative_balance_policy(negative_balance)
+                    .with_invariant_checks()
+            };
+            let finished = ParallelExecutor::new(3)
+                .try_run_from(
+                    executor(),
+                    (1..).zip(inputs.iter().copied()).map(Ok::<_, ()>),
+                )
+                .unwrap();
+            let mut executor = executor();
+            for (row, i) in (1..).zip(inputs.iter()) {
+                let _ = executor.process_at(row, *i);
+            }
+            assert_eq!(executor.violations(), &[]);
+            assert_eq!(finished.into_executor().violations(), &[]);
+        }
+    }
+}
+
+#[test]
+fn invariants_report_the_input() {
+    use transaction::*;
+
+    let input = |ty, tx, amount| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    //a legacy withdrawl dispute holds a negative amount
+    let mut executor = Executor::default();
+    executor
+        .process(input(InputType::Deposit, 1, Some(Amount(5_0000))))
+        .unwrap();
+    executor
+        .process(input(InputType::Withdrawl, 2, Some(Amount(2_0000))))
+        .unwrap();
+    executor
+        .process(input(InputType::Dispute, 2, None))
+        .unwrap();
+
+    //which breaks the invariants of a policy holding withdrawl disputes positive
+    let mut executor = executor
+        .with_dispute_policy(WithdrawalToHeld)
+        .with_invariant_checks();
+    let deposit = input(InputType::Deposit, 3, Some(Amount(1_0000)));
+    executor.process_at(10, deposit).unwrap();
+    let broken: Vec<_> = executor.violations().iter().map(|x| x.invariant).collect();
+    assert_eq!(
+        broken,
+        vec![Invariant::HeldNonNegative, Invariant::PendingHeld]
+    );
+    let violation = executor.violations()[0];
+    assert_eq!(violation.position, 10);
+    assert_eq!(violation.input, InputInternal::try_from(deposit).unwrap());
+    assert_eq!(
+        violation.to_string(),
+        "held is negative for client 1 after input at 10: Deposit(Client(1), Tx(3), Amount(10000), Eligible)"
+    );
+}
+
+#[test]
+fn invariants_hold_for_a_tx_id_of_two_clients() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount
+deposit,1,5,10.0
+dispute,1,5,
+deposit,2,5,4.0
+resolve,1,5,
+";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let mut executor = Executor::default().with_invariant_checks();
+    for x in reader {
+        let (row, input) = x.unwrap();
+        executor.process_at(row, input).unwrap();
+    }
+    //client 2's deposit leaves the dispute of client 1's tx 5 in place
+    assert!(executor.violations().is_empty());
+    assert_eq!(
+        executor.tx_disputed(Client(1), Tx(5)),
+        Some(DisputedAmounts {
+            held: Amount::ZERO,
+            charged_back: Amount::ZERO,
+        })
+    );
+}
@@ -0,0 +1,272 @@
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+use common::InputGen;
+
+#[test]
+fn journal_entries_balance() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount,to,currency
+deposit,1,1,10.0,,
+deposit,2,2,5.0,,EUR
+dispute,1,1,4.0,,
+transfer,2,3,1.0,1,EUR
+chargeback,1,1,,,
+lock,2,4,,,
+";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let mut executor = Executor::default().with_journal();
+    for x in reader {
+        let (row, input) = x.unwrap();
+        executor.process_at(row, input).unwrap();
+    }
+    executor.check_journal().unwrap();
+
+    let journal = executor.journal().unwrap();
+    assert_eq!(journal.len(), 6);
+    assert!(journal.iter().all(|x| x.sum() == Some(Amount::ZERO)));
+    let client = |kind, client: u16, currency| Account {
+        kind,
+        client: Some(Client(client)),
+        currency,
+    };
+    let omnibus = Account {
+        kind: AccountKind::Omnibus,
+        client: None,
+        currency: None,
+    };
+    let eur = Some("EUR".parse().unwrap());
+    let postings = |i: usize| -> Vec<(Account, Amount)> {
+        journal[i]
+            .postings
+            .iter()
+            .map(|x| (x.account, x.amount))
+            .collect()
+    };
+    assert_eq!(
+        postings(0),
+        vec![
+            (client(AccountKind::Available, 1, None), Amount(-10_0000)),
+            (omnibus, Amount(10_0000)),
+        ]
+    );
+    assert_eq!(
+        postings(2),
+        vec![
+            (client(AccountKind::Available, 1, None), Amount(4_0000)),
+            (client(AccountKind::Held, 1, None), Amount(-4_0000)),
+        ]
+    );
+    assert_eq!(
+        postings(3),
+        vec![
+            (client(AccountKind::Available, 2, eur), Amount(1_0000)),
+            (client(AccountKind::Available, 1, eur), Amount(-1_0000)),
+        ]
+    );
+    assert_eq!(
+        postings(4),
+        vec![
+            (client(AccountKind::Held, 1, None), Amount(4_0000)),
+            (omnibus, Amount(-4_0000)),
+        ]
+    );
+    assert_eq!(journal[5].applied, Some(Applied::Lock));
+    assert!(journal[5].postings.is_empty());
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for line in journal[0].lines() {
+        writer.serialize(line).unwrap();
+    }
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "position,tx,applied,account,client,currency,amount\n\
+         2,1,deposit,available,1,,-10.0000\n\
+         2,1,deposit,omnibus,,,10.0000\n"
+    );
+
+    assert_eq!(
+        Executor::default().check_journal(),
+        Err(JournalError::NotKept)
+    );
+}
+
+#[test]
+fn journal_across_shards_and_restores() {
+    use transaction::*;
+
+    let mut gen = InputGen::new(5).with_types(&[
+        (InputType::Deposit, 2),
+        (InputType::Withdrawl, 1),
+        (InputType::Dispute, 1),
+        (InputType::Resolve, 1),
+        (InputType::Chargeback, 1),
+        (InputType::Transfer, 1),
+    ]);
+    let inputs = gen.inputs(3000);
+    let mut executor = Executor::default().with_journal();
+    for (row, i) in (1..).zip(inputs.iter()) {
+        let _ = executor.process_at(row, *i);
+    }
+    executor.check_journal().unwrap();
+
+    //transfers between workers are journaled in halves and joined when merged
+    let finished = ParallelExecutor::new(3)
+        .with_batch_size(16)
+        .try_run_from(
+            Executor::default().with_journal(),
+            (1..).zip(inputs.iter().copied()).map(Ok::<_, ()>),
+        )
+        .unwrap();
+    let merged = finished.into_executor();
+    merged.check_journal().unwrap();
+    let sums = |x: &Executor| -> Vec<(u64, usize)> {
+        x.journal()
+            .unwrap()
+            .iter()
+            .map(|x| (x.position, x.postings.len()))
+            .collect()
+    };
+    assert_eq!(sums(&merged), sums(&executor));
+
+    //a restored state opens its journal with the balances
+    let mut snapshot = vec![];
+    executor.snapshot(&mut snapshot).unwrap();
+    let mut restored = Executor::restore(&snapshot[..]).unwrap().with_journal();
+    assert_eq!(restored.journal().unwrap()[0].tx, None);
+    for (row, i) in (3001..).zip(gen.inputs(500)) {
+        let _ = restored.process_at(row, i);
+    }
+    restored.check_journal().unwrap();
+}
+
+#[test]
+fn journal_posts_what_policies_move() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount
+deposit,1,1,10.0
+withdrawal,1,2,8.0
+dispute,1,1,
+chargeback,1,1,
+";
+    let run = |executor: Executor| {
+        let mut executor = executor.with_journal();
+        let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+        for x in reader {
+            let (row, input) = x.unwrap();
+            executor.process_at(row, input).unwrap();
+        }
+        executor
+    };
+    let held = |kind, amount| Posting {
+        account: Account {
+            kind,
+            client: Some(Client(1)),
+            currency: None,
+        },
+        amount,
+    };
+
+    //a partial hold posts only what was held, the chargeback releases the same
+    let executor =
+        run(Executor::default().with_negative_balance_policy(NegativeBalancePolicy::PartialHold));
+    executor.check_journal().unwrap();
+    let journal = executor.journal().unwrap();
+    assert_eq!(
+        journal[2].postings,
+        vec![
+            held(AccountKind::Available, Amount(2_0000)),
+            held(AccountKind::Held, Amount(-2_0000)),
+        ]
+    );
+    //what wasn't held comes out of available
+    assert_eq!(
+        journal[3].postings,
+        vec![
+            held(AccountKind::Available, Amount(8_0000)),
+            held(AccountKind::Held, Amount(2_0000)),
+            Posting {
+                account: Account {
+                    kind: AccountKind::Omnibus,
+                    client: None,
+                    currency: None,
+                },
+                amount: Amount(-10_0000),
+            },
+        ]
+    );
+
+    //a policy whose deltas change total by more than available and held doesn't reconcile
+    #[derive(Debug)]
+    struct Leaky;
+    impl DisputePolicy for Leaky {
+        fn dispute(&self, _: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+            Ok((Amount(-amount.0), amount, amount))
+        }
+        fn resolve(&self, _: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+            Ok((amount, Amount(-amount.0), Amount::ZERO))
+        }
+        fn chargeback(&self, _: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+            Ok((Amount::ZERO, Amount(-amount.0), Amount(-amount.0)))
+        }
+    }
+    let executor = run(Executor::default().with_dispute_policy(Leaky));
+    assert_eq!(
+        executor.check_journal(),
+        Err(JournalError::Unbalanced {
+            position: 4,
+            tx: Some(Tx(1)),
+        })
+    );
+}
+
+#[test]
+fn journal_reports_overflow() {
+    use transaction::*;
+
+    let deposit = |client, tx, amount| Input {
+        ty: InputType::Deposit,
+        client: Client(client),
+        tx: Tx(tx),
+        amount: Some(Amount(amount)),
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let inputs = [deposit(1, 1, i64::MAX), deposit(2, 2, 2)];
+
+    //what the omnibus account holds for both is out of range
+    let mut executor = Executor::default().with_journal();
+    for x in inputs {
+        executor.process(x).unwrap();
+    }
+    assert_eq!(executor.journal().unwrap().len(), 2);
+    assert_eq!(
+        executor.check_journal(),
+        Err(JournalError::Overflow {
+            position: 2,
+            tx: Some(Tx(2)),
+        })
+    );
+
+    //as are the opening balances of a journal kept from there on
+    let mut executor = Executor::default();
+    for x in inputs {
+        executor.process(x).unwrap();
+    }
+    let executor = executor.with_journal();
+    assert_eq!(executor.journal().unwrap().len(), 0);
+    assert_eq!(
+        executor.check_journal(),
+        Err(JournalError::Overflow {
+            position: 2,
+            tx: None,
+        })
+    );
+}
@@ -0,0 +1,356 @@
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+use common::InputGen;
+
+#[cfg(test)]
+fn sorted(mut out: Vec<transaction::Output>) -> Vec<transaction::Output> {
+    out.sort_by_key(|x| x.client.0);
+    out
+}
+
+#[test]
+fn parallel_matches_single_executor() {
+    use transaction::*;
+
+    let inputs = InputGen::new(7).with_clients(20).inputs(5000);
+
+    let mut executor = Executor::default();
+    let mut rejected_rows = vec![];
+    for (row, i) in (1..).zip(inputs.iter()) {
+        if executor.process(*i).is_err() {
+            rejected_rows.push(row);
+        }
+    }
+    let expected = sorted(executor.output().collect());
+
+    for batch_size in [1, 7, 256, 10_000] {
+        let finished = ParallelExecutor::new(3)
+            .with_queue_capacity(2)
+            .with_batch_size(batch_size)
+            .run(inputs.iter().copied());
+        assert_eq!(sorted(finished.outputs), expected);
+        let rows: Vec<u64> = finished.rejections.iter().map(|x| x.row).collect();
+        assert_eq!(rows, rejected_rows);
+    }
+}
+
+#[test]
+fn parallel_matches_single_executor_on_shared_tx_ids() {
+    use transaction::*;
+
+    //tx 5 is a deposit of both clients, which land on different workers
+    let data = "\
+type,client,tx,amount
+deposit,1,5,10.0
+deposit,2,5,4.0
+dispute,1,5,
+dispute,2,5,
+chargeback,2,5,
+withdrawal,1,5,1.0
+";
+    let inputs: Vec<_> = InputReader::from_reader(data.as_bytes(), IngestMode::Strict)
+        .unwrap()
+        .map(|x| x.unwrap().1)
+        .collect();
+    let mut executor = Executor::default();
+    let mut rejected_rows = vec![];
+    for (row, i) in (1..).zip(inputs.iter()) {
+        if executor.process(*i).is_err() {
+            rejected_rows.push(row);
+        }
+    }
+    assert_eq!(rejected_rows, vec![6]);
+    let expected = sorted(executor.output().collect());
+    assert_eq!(expected[0].held, Amount(10_0000));
+    assert!(expected[1].locked);
+
+    let finished = ParallelExecutor::new(2).run(inputs);
+    assert_eq!(sorted(finished.outputs), expected);
+    let rows: Vec<u64> = finished.rejections.iter().map(|x| x.row).collect();
+    assert_eq!(rows, rejected_rows);
+}
+
+#[test]
+fn parallel_backpressure_stats() {
+    use transaction::*;
+
+    let inputs = InputGen::new(7).with_clients(20).inputs(2000);
+    let finished = ParallelExecutor::new(2)
+        .with_queue_capacity(1)
+        .with_batch_size(1)
+        .run(inputs);
+    assert_eq!(finished.stats.len(), 2);
+    assert_eq!(finished.stats.iter().map(|x| x.inputs).sum::<u64>(), 2000);
+    assert_eq!(finished.stats.iter().map(|x| x.batches).sum::<u64>(), 2000);
+    for i in &finished.stats {
+        assert!(i.stalls <= i.inputs);
+        if i.stalls == 0 {
+            assert_eq!(i.stalled, std::time::Duration::ZERO);
+        }
+    }
+}
+
+#[test]
+fn parallel_backpressure_stalls_on_full_queue() {
+    use transaction::*;
+
+    //a worker slow to take disputes falls behind a feeder that never waits
+    #[derive(Debug)]
+    struct Slow;
+    impl DisputePolicy for Slow {
+        fn dispute(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            DepositsOnly.dispute(kind, amount)
+        }
+        fn resolve(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+            DepositsOnly.resolve(kind, amount)
+        }
+        fn chargeback(&self, kind: Disputed, amount: Amount) -> Result<Deltas, Rejection> {
+            DepositsOnly.chargeback(kind, amount)
+        }
+    }
+
+    let input = |ty, tx| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount: (ty == InputType::Deposit).then_some(Amount(1_0000)),
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let deposits = (1..=20).map(|x| input(InputType::Deposit, x));
+    let disputes = (1..=20).map(|x| input(InputType::Dispute, x));
+    let rows = (1..).zip(deposits.chain(disputes));
+    let finished = ParallelExecutor::new(1)
+        .with_queue_capacity(1)
+        .with_batch_size(1)
+        .try_run_from(
+            Executor::default().with_dispute_policy(Slow),
+            rows.map(Ok::<_, std::convert::Infallible>),
+        )
+        .unwrap();
+    assert_eq!(finished.stats.len(), 1);
+    assert_eq!(finished.stats[0].inputs, 40);
+    assert!(finished.stats[0].stalls > 0);
+    assert!(finished.stats[0].stalled > std::time::Duration::ZERO);
+}
+
+#[test]
+fn parallel_from_channel() {
+    use transaction::*;
+
+    let inputs = InputGen::new(7).with_clients(20).inputs(1000);
+    let mut executor = Executor::default();
+    for i in inputs.iter() {
+        let _ = executor.process(*i);
+    }
+    let expected = sorted(executor.output().collect());
+
+    let (sender, receiver) = crossbeam::channel::unbounded();
+    let producer = std::thread::spawn(move || {
+        for i in inputs {
+            sender.send(i).unwrap();
+        }
+    });
+    let finished = ParallelExecutor::new(4).run(receiver);
+    producer.join().unwrap();
+    assert_eq!(sorted(finished.outputs), expected);
+}
+
+#[test]
+fn parallel_stops_at_error() {
+    use transaction::*;
+
+    let inputs = InputGen::new(7).with_clients(20).inputs(10);
+    let rows = (1..)
+        .zip(inputs)
+        .map(|(row, x)| if row == 5 { Err(row) } else { Ok((row, x)) });
+    let res = ParallelExecutor::new(2).try_run(rows);
+    assert_eq!(res.unwrap_err(), 5);
+}
+
+#[cfg(test)]
+fn write_csv(name: &str, inputs: &[transaction::Input]) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("{}_{}.csv", name, std::process::id()));
+    let mut writer = csv::Writer::from_path(&path).unwrap();
+    for i in inputs {
+        writer.serialize(i).unwrap();
+    }
+    writer.flush().unwrap();
+    path
+}
+
+#[test]
+fn parallel_file_matches_single_executor() {
+    use transaction::*;
+
+    let inputs = InputGen::new(7).with_clients(20).inputs(3000);
+    let path = write_csv("parallel_file_matches", &inputs);
+
+    let mut executor = Executor::default();
+    let mut rejected_rows = vec![];
+    for (row, i) in (2..).zip(inputs.iter()) {
+        if executor.process(*i).is_err() {
+            rejected_rows.push(row);
+        }
+    }
+    let expected = sorted(executor.output().collect());
+
+    for (parsers, chunk_size) in [(1, 1 << 20), (3, 100), (4, 16), (2, 4096)] {
+        let finished = ParallelExecutor::new(3)
+            .with_queue_capacity(1)
+            .with_parser_threads(parsers)
+            .with_chunk_size(chunk_size)
+            .run_file(&path, IngestMode::Strict)
+            .unwrap();
+        assert_eq!(sorted(finished.outputs), expected);
+        let rows: Vec<u64> = finished.rejections.iter().map(|x| x.row).collect();
+        assert_eq!(rows, rejected_rows);
+        assert_eq!(finished.stats.iter().map(|x| x.inputs).sum::<u64>(), 3000);
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn parallel_file_bad_rows() {
+    use transaction::*;
+
+    let mut data = String::from("type, client, tx, amount\n");
+    for i in 1..=200 {
+        if i % 50 == 0 {
+            data.push_str("deposit, x, 1, 1.0\n");
+        } else {
+            data.push_str(&format!(" deposit , {}, {}, 1.5\n", i % 7, i));
+        }
+    }
+    let path = std::env::temp_dir().join(format!("parallel_file_bad_{}.csv", std::process::id()));
+    std::fs::write(&path, data).unwrap();
+
+    let executor = ParallelExecutor::new(2)
+        .with_parser_threads(3)
+        .with_chunk_size(64);
+    let finished = executor.run_file(&path, IngestMode::Lenient).unwrap();
+    let rows: Vec<u64> = finished.skipped.iter().map(|x| x.row).collect();
+    assert_eq!(rows, vec![51, 101, 151, 201]);
+    let total: i64 = finished.outputs.iter().map(|x| x.total.0).sum();
+    assert_eq!(total, 196 * 1_5000);
+
+    let err = executor.run_file(&path, IngestMode::Strict).unwrap_err();
+    assert_eq!(err.row, 51);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn parallel_file_rejects_invalid_amounts() {
+    use transaction::*;
+
+    let mut data = String::from("type, client, tx, amount\n");
+    for i in 1..=200 {
+        match i % 40 {
+            0 => data.push_str(&format!("deposit, {}, {}, 1.00001\n", i % 7, i)),
+            20 => data.push_str(&format!("withdrawal, {}, {}, 1000.0\n", i % 7, i)),
+            _ => data.push_str(&format!("deposit, {}, {}, 1.5\n", i % 7, i)),
+        }
+    }
+    let path =
+        std::env::temp_dir().join(format!("parallel_file_amounts_{}.csv", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+
+    let expected: Vec<_> = [21, 41, 61, 81, 101, 121, 141, 161, 181, 201]
+        .into_iter()
+        .map(|row| {
+            let amount = if row % 40 == 1 {
+                "1.00001"
+            } else {
+                "1000.0000"
+            };
+            (row, amount.to_string())
+        })
+        .collect();
+    let executor = ParallelExecutor::new(2)
+        .with_parser_threads(3)
+        .with_chunk_size(64);
+    let from_file = executor.run_file(&path, IngestMode::Strict).unwrap();
+    let from_reader = executor
+        .run_reader(data.as_bytes(), IngestMode::Strict)
+        .unwrap();
+    for finished in [from_file, from_reader] {
+        let rows: Vec<_> = finished
+            .rejections
+            .iter()
+            .map(|x| (x.row, x.amount.clone().unwrap()))
+            .collect();
+        assert_eq!(rows, expected);
+        let total: i64 = finished.outputs.iter().map(|x| x.total.0).sum();
+        assert_eq!(total, 190 * 1_5000);
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn parallel_from_reader() {
+    use transaction::*;
+
+    let data = "type,client,tx,amount\ndeposit,1,1,2.0\ndeposit,x,2,1.0\nwithdrawal,1,3,0.5\n";
+    let finished = ParallelExecutor::new(2)
+        .run_reader(data.as_bytes(), IngestMode::Lenient)
+        .unwrap();
+    assert_eq!(finished.outputs.len(), 1);
+    assert_eq!(finished.outputs[0].total, Amount(1_5000));
+    let rows: Vec<u64> = finished.skipped.iter().map(|x| x.row).collect();
+    assert_eq!(rows, vec![3]);
+
+    let err = ParallelExecutor::new(2)
+        .run_reader(data.as_bytes(), IngestMode::Strict)
+        .unwrap_err();
+    assert_eq!(err.row, 3);
+}
+
+#[test]
+fn parallel_transfers_between_workers() {
+    use transaction::*;
+
+    let inputs = InputGen::new(11)
+        .with_types(&[(InputType::Deposit, 1), (InputType::Transfer, 2)])
+        .with_clients(20)
+        .with_amounts(1..100_0000)
+        .inputs(3000);
+    let path = write_csv("parallel_transfers", &inputs);
+
+    let mut executor = Executor::default();
+    let mut rejected_rows = vec![];
+    for (row, i) in (1..).zip(inputs.iter()) {
+        if executor.process(*i).is_err() {
+            rejected_rows.push(row);
+        }
+    }
+    assert!(rejected_rows.len() > 100);
+    let expected = sorted(executor.output().collect());
+
+    for (workers, capacity, batch_size) in [(2, 0, 1), (3, 2, 7), (4, 1, 256), (7, 64, 10_000)] {
+        let finished = ParallelExecutor::new(workers)
+            .with_queue_capacity(capacity)
+            .with_batch_size(batch_size)
+            .run(inputs.iter().copied());
+        assert_eq!(sorted(finished.outputs), expected);
+        let rows: Vec<u64> = finished.rejections.iter().map(|x| x.row).collect();
+        assert_eq!(rows, rejected_rows);
+    }
+
+    let header_rows: Vec<u64> = rejected_rows.iter().map(|x| x + 1).collect();
+    for (parsers, chunk_size) in [(1, 1 << 20), (3, 100), (4, 16)] {
+        let finished = ParallelExecutor::new(3)
+            .with_queue_capacity(1)
+            .with_parser_threads(parsers)
+            .with_chunk_size(chunk_size)
+            .run_file(&path, IngestMode::Strict)
+            .unwrap();
+        assert_eq!(sorted(finished.outputs), expected);
+        let rows: Vec<u64> = finished.rejections.iter().map(|x| x.row).collect();
+        assert_eq!(rows, header_rows);
+    }
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,194 @@
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+use common::InputGen;
+
+#[test]
+fn snapshot_restore_resumes() {
+    use transaction::*;
+
+    let mut inputs = InputGen::new(0);
+    let first = inputs.inputs(2000);
+    let second = inputs.inputs(2000);
+
+    let mut full =
+        Executor::default().with_negative_balance_policy(NegativeBalancePolicy::PartialHold);
+    let mut expected = vec![];
+    for i in first.iter().chain(second.iter()) {
+        expected.push(full.process(*i));
+    }
+
+    let mut executor =
+        Executor::default().with_negative_balance_policy(NegativeBalancePolicy::PartialHold);
+    for i in first.iter() {
+        let _ = executor.process(*i);
+    }
+    executor.set_checkpoint(Some(Checkpoint {
+        byte: 4096,
+        line: 2001,
+        record: 2001,
+    }));
+    let mut bytes = vec![];
+    executor.snapshot(&mut bytes).unwrap();
+    let mut again = vec![];
+    executor.snapshot(&mut again).unwrap();
+    assert_eq!(bytes, again);
+
+    //settings are not part of the state
+    let mut restored = Executor::restore(&bytes[..])
+        .unwrap()
+        .with_negative_balance_policy(NegativeBalancePolicy::PartialHold);
+    assert_eq!(restored, executor);
+    let resumed: Vec<_> = second.iter().map(|i| restored.process(*i)).collect();
+    assert_eq!(resumed, expected[first.len()..]);
+    restored.set_checkpoint(None);
+    assert_eq!(restored, full);
+}
+
+#[test]
+fn snapshot_rejects_bad_input() {
+    use transaction::*;
+
+    let mut executor = Executor::default();
+    for i in InputGen::new(0).inputs(100) {
+        let _ = executor.process(i);
+    }
+    let mut bytes = vec![];
+    executor.snapshot(&mut bytes).unwrap();
+
+    let mut bad = bytes.clone();
+    bad[0] = b'x';
+    assert!(matches!(
+        Executor::restore(&bad[..]),
+        Err(SnapshotError::BadMagic)
+    ));
+
+    //only the current format is read, older ones never shipped
+    for version in [1, 11, 99] {
+        let mut bad = bytes.clone();
+        bad[8..10].copy_from_slice(&u16::to_le_bytes(version));
+        assert!(matches!(
+            Executor::restore(&bad[..]),
+            Err(SnapshotError::UnsupportedVersion(x)) if x == version
+        ));
+    }
+
+    assert!(matches!(
+        Executor::restore(&bytes[..bytes.len() - 1]),
+        Err(SnapshotError::Io(_))
+    ));
+
+    let mut bad = bytes.clone();
+    bad.push(0);
+    assert!(matches!(
+        Executor::restore(&bad[..]),
+        Err(SnapshotError::Corrupt(_))
+    ));
+}
+
+#[test]
+fn snapshot_parallel_resume() {
+    use transaction::*;
+
+    let mut inputs = InputGen::new(0);
+    let first = inputs.inputs(3000);
+    let second = inputs.inputs(3000);
+
+    let mut full = Executor::default();
+    for i in first.iter().chain(second.iter()) {
+        let _ = full.process(*i);
+    }
+
+    let parallel = ParallelExecutor::new(3).with_batch_size(16);
+    let state = parallel.run(first).into_executor();
+    let mut bytes = vec![];
+    state.snapshot(&mut bytes).unwrap();
+
+    let restored = Executor::restore(&bytes[..]).unwrap();
+    let rows = (3001..).zip(second).map(Ok::<_, ()>);
+    let finished = parallel.try_run_from(restored, rows).unwrap();
+    assert_eq!(finished.into_executor(), full);
+
+    let parts = Executor::restore(&bytes[..]).unwrap().split(4);
+    assert_eq!(Executor::merge(parts), state);
+}
+
+#[test]
+fn snapshot_keeps_tx_history() {
+    use transaction::*;
+
+    let mut inputs = InputGen::new(0);
+    let first = inputs.inputs(1000);
+    let second = inputs.inputs(1000);
+
+    let mut full = Executor::default().with_tx_history();
+    for i in first.iter().chain(second.iter()) {
+        let _ = full.process(*i);
+    }
+
+    let mut executor = Executor::default().with_tx_history();
+    for i in first.iter() {
+        let _ = executor.process(*i);
+    }
+    let mut bytes = vec![];
+    executor.snapshot(&mut bytes).unwrap();
+    let mut restored = Executor::restore(&bytes[..]).unwrap();
+    assert!(restored.tx_history_kept());
+    assert_eq!(restored, executor);
+    for i in second.iter() {
+        let _ = restored.process(*i);
+    }
+    assert_eq!(restored, full);
+    assert_eq!(restored.tx_histories(), full.tx_histories());
+
+    let merged = Executor::merge(restored.split(3));
+    assert_eq!(merged, full);
+}
+
+#[test]
+fn snapshot_keeps_currencies() {
+    use transaction::*;
+
+    let currencies = [
+        None,
+        Some("USD".parse().unwrap()),
+        Some("BTC".parse().unwrap()),
+    ];
+    let inputs: Vec<_> = InputGen::new(0)
+        .inputs(3000)
+        .into_iter()
+        .enumerate()
+        .map(|(i, x)| Input {
+            currency: currencies[i % 3],
+            ..x
+        })
+        .collect();
+    let (first, second) = inputs.split_at(1500);
+
+    let mut full = Executor::default();
+    for i in inputs.iter() {
+        let _ = full.process(*i);
+    }
+    let mut executor = Executor::default();
+    for i in first {
+        let _ = executor.process(*i);
+    }
+    let mut bytes = vec![];
+    executor.snapshot(&mut bytes).unwrap();
+    let mut restored = Executor::restore(&bytes[..]).unwrap();
+    assert_eq!(restored, executor);
+    for i in second {
+        let _ = restored.process(*i);
+    }
+    assert_eq!(restored, full);
+
+    let mut merged = Executor::merge(restored.split(4));
+    assert_eq!(merged, full);
+    let mut out: Vec<_> = merged.output().collect();
+    out.sort_by_key(|x| (x.client.0, x.currency));
+    let mut expected: Vec<_> = full.output().collect();
+    expected.sort_by_key(|x| (x.client.0, x.currency));
+    assert_eq!(out, expected);
+    assert!(out.iter().any(|x| x.currency.is_some()));
+}
@@ -10,29 +10,36 @@ fn transaction_deposits() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(7.)),
+            amount: Some(Amount(7_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
 
     let expected = Output {
         client: Client(1),
-        available: Amount(12.),
-        held: Amount(0.),
-        total: Amount(12.),
+        available: Amount(12_0000),
+        held: Amount(0),
+        total: Amount(12_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -46,28 +53,35 @@ fn transaction_deposit_withdrawl() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(2.),
-        held: Amount(0.),
-        total: Amount(2.),
+        available: Amount(2_0000),
+        held: Amount(0),
+        total: Amount(2_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -81,28 +95,34 @@ fn transaction_deposit_withdrawl_insufficient_fund() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(8.)),
+            amount: Some(Amount(8_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
-    for i in inputs {
-        executor.process(i);
-    }
+    let outcomes: Vec<_> = inputs.into_iter().map(|i| executor.process(i)).collect();
+    assert_eq!(outcomes.last(), Some(&Err(Rejection::InsufficientFunds)));
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(5.),
-        held: Amount(0.),
-        total: Amount(5.),
+        available: Amount(5_0000),
+        held: Amount(0),
+        total: Amount(5_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -116,19 +136,24 @@ fn transaction_deposit_withdrawl_insufficient_fund_2() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(2),
             tx: Tx(2),
-            amount: Some(Amount(8.)),
+            amount: Some(Amount(8_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
-    for i in inputs {
-        executor.process(i);
-    }
+    let outcomes: Vec<_> = inputs.into_iter().map(|i| executor.process(i)).collect();
+    assert_eq!(outcomes.last(), Some(&Err(Rejection::InsufficientFunds)));
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 2);
 
@@ -144,20 +169,22 @@ fn transaction_deposit_withdrawl_insufficient_fund_2() {
         hm.get(&Client(1)).unwrap(),
         &Output {
             client: Client(1),
-            available: Amount(5.),
-            held: Amount(0.),
-            total: Amount(5.),
+            available: Amount(5_0000),
+            held: Amount(0),
+            total: Amount(5_0000),
             locked: false,
+            currency: None,
         }
     );
     assert_eq!(
         hm.get(&Client(2)).unwrap(),
         &Output {
             client: Client(2),
-            available: Amount(0.),
-            held: Amount(0.),
-            total: Amount(0.),
+            available: Amount(0),
+            held: Amount(0),
+            total: Amount(0),
             locked: false,
+            currency: None,
         }
     );
 }
@@ -170,21 +197,24 @@ fn transaction_withdrawl_invalid() {
         ty: InputType::Withdrawl,
         client: Client(1),
         tx: Tx(1),
-        amount: Some(Amount(8.)),
+        amount: Some(Amount(8_0000)),
+        timestamp: None,
+        currency: None,
+        to: None,
     }];
     let mut executor = Executor::default();
-    for i in inputs {
-        executor.process(i);
-    }
+    let outcomes: Vec<_> = inputs.into_iter().map(|i| executor.process(i)).collect();
+    assert_eq!(outcomes.last(), Some(&Err(Rejection::InsufficientFunds)));
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(0.),
-        held: Amount(0.),
-        total: Amount(0.),
+        available: Amount(0),
+        held: Amount(0),
+        total: Amount(0),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -198,34 +228,44 @@ fn transaction_dispute_for_withdrawl() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(5.),
-        held: Amount(-3.),
-        total: Amount(2.),
+        available: Amount(5_0000),
+        held: Amount(-3_0000),
+        total: Amount(2_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -239,40 +279,53 @@ fn transaction_dispute_for_deposit() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(3),
-            amount: Some(Amount(10.)),
+            amount: Some(Amount(10_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(7.),
-        held: Amount(5.),
-        total: Amount(12.),
+        available: Amount(7_0000),
+        held: Amount(5_0000),
+        total: Amount(12_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -286,25 +339,37 @@ fn transaction_multiple_disputes_for_same_transaction() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(3),
-            amount: Some(Amount(10.)),
+            amount: Some(Amount(10_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             //duplicate dispute should be idempotent
@@ -312,21 +377,24 @@ fn transaction_multiple_disputes_for_same_transaction() {
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
-    for i in inputs {
-        executor.process(i);
-    }
+    let outcomes: Vec<_> = inputs.into_iter().map(|i| executor.process(i)).collect();
+    assert_eq!(outcomes.last(), Some(&Err(Rejection::AlreadyDisputed)));
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(7.),
-        held: Amount(5.),
-        total: Amount(12.),
+        available: Amount(7_0000),
+        held: Amount(5_0000),
+        total: Amount(12_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -340,40 +408,53 @@ fn transaction_resolve_for_withdrawl() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Resolve,
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(2.),
-        held: Amount(0.),
-        total: Amount(2.),
+        available: Amount(2_0000),
+        held: Amount(0),
+        total: Amount(2_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -387,46 +468,62 @@ fn transaction_resolve_for_deposit() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(3),
-            amount: Some(Amount(10.)),
+            amount: Some(Amount(10_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Resolve,
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(12.),
-        held: Amount(0.),
-        total: Amount(12.),
+        available: Amount(12_0000),
+        held: Amount(0),
+        total: Amount(12_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -440,40 +537,53 @@ fn transaction_chargeback_for_withdrawl() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Chargeback,
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(5.),
-        held: Amount(0.),
-        total: Amount(5.),
+        available: Amount(5_0000),
+        held: Amount(0),
+        total: Amount(5_0000),
         locked: true,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -487,25 +597,37 @@ fn transaction_resolve_and_chargeback_for_withdrawl() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Resolve,
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         //dispute it again
         Input {
@@ -513,27 +635,34 @@ fn transaction_resolve_and_chargeback_for_withdrawl() {
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Chargeback,
             client: Client(1),
             tx: Tx(2),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(5.),
-        held: Amount(0.),
-        total: Amount(5.),
+        available: Amount(5_0000),
+        held: Amount(0),
+        total: Amount(5_0000),
         locked: true,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -547,46 +676,62 @@ fn transaction_chargeback_for_deposit() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Withdrawl,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(3.)),
+            amount: Some(Amount(3_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(3),
-            amount: Some(Amount(10.)),
+            amount: Some(Amount(10_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Chargeback,
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
     for i in inputs {
-        executor.process(i);
+        executor.process(i).unwrap();
     }
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(7.),
-        held: Amount(0.),
-        total: Amount(7.),
+        available: Amount(7_0000),
+        held: Amount(0),
+        total: Amount(7_0000),
         locked: true,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -600,46 +745,61 @@ fn transaction_activity_after_locked() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(10.)),
+            amount: Some(Amount(10_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Dispute,
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Chargeback,
             client: Client(1),
             tx: Tx(1),
             amount: None,
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(3),
-            amount: Some(Amount(20.)),
+            amount: Some(Amount(20_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
-    for i in inputs {
-        executor.process(i);
-    }
+    let outcomes: Vec<_> = inputs.into_iter().map(|i| executor.process(i)).collect();
+    assert_eq!(outcomes.last(), Some(&Err(Rejection::AccountLocked)));
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(10.),
-        held: Amount(0.),
-        total: Amount(10.),
+        available: Amount(10_0000),
+        held: Amount(0),
+        total: Amount(10_0000),
         locked: true,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
@@ -653,34 +813,1090 @@ fn transaction_duplicate_tx_id() {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1),
-            amount: Some(Amount(5.)),
+            amount: Some(Amount(5_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(2),
-            amount: Some(Amount(10.)),
+            amount: Some(Amount(10_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
         Input {
             ty: InputType::Deposit,
             client: Client(1),
             tx: Tx(1), //duplcate id shouldn't be in input data, so should ignore it
-            amount: Some(Amount(20.)),
+            amount: Some(Amount(20_0000)),
+            timestamp: None,
+            currency: None,
+            to: None,
         },
     ];
     let mut executor = Executor::default();
-    for i in inputs {
-        executor.process(i);
-    }
+    let outcomes: Vec<_> = inputs.into_iter().map(|i| executor.process(i)).collect();
+    assert_eq!(outcomes.last(), Some(&Err(Rejection::DuplicateTx)));
     let out: Vec<_> = executor.output().collect();
     assert_eq!(out.len(), 1);
-    let item = out.get(0).unwrap();
+    let item = out.first().unwrap();
     let expected = Output {
         client: Client(1),
-        available: Amount(15.),
-        held: Amount(0.),
-        total: Amount(15.),
+        available: Amount(15_0000),
+        held: Amount(0),
+        total: Amount(15_0000),
         locked: false,
+        currency: None,
     };
     assert_eq!(item, &expected);
 }
+
+#[test]
+fn amount_parse_and_format() {
+    use transaction::*;
+
+    assert_eq!("1.2345".parse::<Amount>(), Ok(Amount(1_2345)));
+    assert_eq!("-0.5".parse::<Amount>(), Ok(Amount(-5000)));
+    assert_eq!("7".parse::<Amount>(), Ok(Amount(7_0000)));
+    assert_eq!(
+        "1.23456".parse::<Amount>(),
+        Err(AmountParseError::TooPrecise)
+    );
+    assert_eq!("1e3".parse::<Amount>(), Err(AmountParseError::Invalid));
+    assert_eq!("".parse::<Amount>(), Err(AmountParseError::Empty));
+    assert_eq!(
+        "999999999999999.0".parse::<Amount>(),
+        Err(AmountParseError::Overflow)
+    );
+
+    assert_eq!(Amount(1_2345).to_string(), "1.2345");
+    assert_eq!(Amount(-5000).to_string(), "-0.5000");
+    assert_eq!(Amount(1000000000001).to_string(), "100000000.0001");
+}
+
+#[test]
+fn amount_csv_round_trip() {
+    use transaction::*;
+
+    let data = "type,client,tx,amount\ndeposit,1,1,123456.7891\ndispute,1,1,\n";
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    let inputs: Vec<Input> = reader.deserialize().map(|x| x.unwrap()).collect();
+    assert_eq!(inputs[0].amount, Some(Amount(1234567891)));
+    assert_eq!(inputs[1].amount, None);
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for i in inputs {
+        writer.serialize(i).unwrap();
+    }
+    //optional columns are written too, left empty
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "type,client,tx,amount,timestamp,currency,to\n\
+         deposit,1,1,123456.7891,,,\n\
+         dispute,1,1,,,,\n"
+    );
+}
+
+#[test]
+fn transaction_deposit_overflow() {
+    use transaction::*;
+
+    let mut executor = Executor::default();
+    executor
+        .process(Input {
+            ty: InputType::Deposit,
+            client: Client(1),
+            tx: Tx(1),
+            amount: Some(Amount(i64::MAX - 1)),
+            timestamp: None,
+            currency: None,
+            to: None,
+        })
+        .unwrap();
+    let res = executor.process(Input {
+        ty: InputType::Deposit,
+        client: Client(1),
+        tx: Tx(2),
+        amount: Some(Amount(2)),
+        timestamp: None,
+        currency: None,
+        to: None,
+    });
+    assert_eq!(res, Err(Rejection::Overflow));
+
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(out[0].total, Amount(i64::MAX - 1));
+}
+
+#[test]
+fn transaction_rejection_reasons() {
+    use transaction::*;
+
+    let mut executor = Executor::default();
+    let deposit = |client, tx| Input {
+        ty: InputType::Deposit,
+        client: Client(client),
+        tx: Tx(tx),
+        amount: Some(Amount(5_0000)),
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let action = |ty, client, tx| Input {
+        ty,
+        client: Client(client),
+        tx: Tx(tx),
+        amount: None,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+
+    assert_eq!(executor.process(deposit(1, 1)), Ok(Applied::Deposit));
+    assert_eq!(executor.process(deposit(2, 2)), Ok(Applied::Deposit));
+    assert_eq!(
+        executor.process(action(InputType::Dispute, 1, 99)),
+        Err(Rejection::UnknownTx)
+    );
+    assert_eq!(
+        executor.process(action(InputType::Dispute, 2, 1)),
+        Err(Rejection::UnknownTx)
+    );
+    assert_eq!(
+        executor.process(action(InputType::Resolve, 1, 1)),
+        Err(Rejection::NotDisputed)
+    );
+    assert_eq!(
+        executor.process(action(InputType::Chargeback, 1, 1)),
+        Err(Rejection::NotDisputed)
+    );
+    assert_eq!(
+        executor.process(action(InputType::Dispute, 1, 1)),
+        Ok(Applied::Dispute)
+    );
+    assert_eq!(
+        executor.process(action(InputType::Dispute, 1, 1)),
+        Err(Rejection::AlreadyDisputed)
+    );
+    assert_eq!(
+        executor.process(action(InputType::Chargeback, 1, 1)),
+        Ok(Applied::Chargeback)
+    );
+    assert_eq!(
+        executor.process(deposit(1, 3)),
+        Err(Rejection::AccountLocked)
+    );
+    assert_eq!(
+        executor.process(action(InputType::Resolve, 1, 1)),
+        Err(Rejection::AccountLocked)
+    );
+}
+
+#[test]
+fn transaction_rejection_report_row() {
+    use transaction::*;
+
+    let input = Input {
+        ty: InputType::Withdrawl,
+        client: Client(3),
+        tx: Tx(9),
+        amount: Some(Amount(1_5000)),
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let mut executor = Executor::default();
+    let reason = executor.process(input).unwrap_err();
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer
+        .serialize(RejectedInput::from((7, input, reason)))
+        .unwrap();
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "row,type,client,tx,amount,timestamp,currency,to,reason\n7,withdrawal,3,9,1.5000,,,,insufficient_funds\n"
+    );
+}
+
+#[test]
+fn transaction_rejection_report_transfer_row() {
+    use transaction::*;
+
+    let usd: Currency = "USD".parse().unwrap();
+    let deposit = Input {
+        ty: InputType::Deposit,
+        client: Client(1),
+        tx: Tx(1),
+        amount: Some(Amount(5_0000)),
+        timestamp: Some(100),
+        currency: Some(usd),
+        to: None,
+    };
+    //the client has usd but the transfer is of eur
+    let transfer = Input {
+        ty: InputType::Transfer,
+        tx: Tx(2),
+        amount: Some(Amount(2_0000)),
+        timestamp: Some(160),
+        currency: Some("EUR".parse().unwrap()),
+        to: Some(Client(2)),
+        ..deposit
+    };
+    let mut executor = Executor::default();
+    executor.process(deposit).unwrap();
+    let reason = executor.process(transfer).unwrap_err();
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer
+        .serialize(RejectedInput::from((3, transfer, reason)))
+        .unwrap();
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "row,type,client,tx,amount,timestamp,currency,to,reason\n3,transfer,1,2,2.0000,160,EUR,2,insufficient_funds\n"
+    );
+}
+
+#[test]
+fn transaction_invalid_amounts() {
+    use transaction::*;
+
+    let mut executor = Executor::default();
+    let input = |ty, tx, amount| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+
+    assert_eq!(
+        executor.process(input(InputType::Deposit, 1, Some(Amount(-5_0000)))),
+        Err(Rejection::NonPositiveAmount)
+    );
+    assert_eq!(
+        executor.process(input(InputType::Withdrawl, 2, Some(Amount(0)))),
+        Err(Rejection::NonPositiveAmount)
+    );
+    assert_eq!(
+        executor.process(input(InputType::Deposit, 3, Some(Amount(5_0000)))),
+        Ok(Applied::Deposit)
+    );
+    for ty in [InputType::Resolve, InputType::Chargeback] {
+        assert_eq!(
+            executor.process(input(ty, 3, Some(Amount(1_0000)))),
+            Err(Rejection::UnexpectedAmount)
+        );
+    }
+    assert_eq!(
+        executor.process(input(InputType::Dispute, 3, Some(Amount(0)))),
+        Err(Rejection::NonPositiveAmount)
+    );
+    assert_eq!(
+        executor.process(input(InputType::Dispute, 3, Some(Amount(5_0001)))),
+        Err(Rejection::DisputeTooLarge)
+    );
+
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(out[0].available, Amount(5_0000));
+    assert_eq!(out[0].held, Amount(0));
+}
+
+#[cfg(test)]
+fn dispute_withdrawl(
+    executor: &mut transaction::Executor,
+    steps: &[transaction::InputType],
+) -> Vec<Result<transaction::Applied, transaction::Rejection>> {
+    use transaction::*;
+
+    let input = |ty, tx, amount| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    executor
+        .process(input(InputType::Deposit, 1, Some(Amount(5_0000))))
+        .unwrap();
+    executor
+        .process(input(InputType::Withdrawl, 2, Some(Amount(3_0000))))
+        .unwrap();
+    steps
+        .iter()
+        .map(|ty| executor.process(input(*ty, 2, None)))
+        .collect()
+}
+
+#[test]
+fn transaction_policy_legacy() {
+    use transaction::*;
+
+    let mut executor = Executor::default().with_dispute_policy(LegacyDisputes);
+    let outcomes = dispute_withdrawl(&mut executor, &[InputType::Dispute]);
+    assert_eq!(outcomes, vec![Ok(Applied::Dispute)]);
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(5_0000), Amount(-3_0000), Amount(2_0000))
+    );
+
+    let mut executor = Executor::default();
+    dispute_withdrawl(&mut executor, &[InputType::Dispute, InputType::Chargeback]);
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(5_0000), Amount(0), Amount(5_0000))
+    );
+    assert!(out[0].locked);
+}
+
+#[test]
+fn transaction_policy_deposits_only() {
+    use transaction::*;
+
+    let mut executor = Executor::default().with_dispute_policy(DepositsOnly);
+    let outcomes = dispute_withdrawl(
+        &mut executor,
+        &[
+            InputType::Dispute,
+            InputType::Resolve,
+            InputType::Chargeback,
+        ],
+    );
+    assert_eq!(
+        outcomes,
+        vec![
+            Err(Rejection::NotDisputable),
+            Err(Rejection::NotDisputed),
+            Err(Rejection::NotDisputed)
+        ]
+    );
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(2_0000), Amount(0), Amount(2_0000))
+    );
+
+    //deposits are disputed as before
+    let dispute = Input {
+        ty: InputType::Dispute,
+        client: Client(1),
+        tx: Tx(1),
+        amount: None,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    assert_eq!(executor.process(dispute), Ok(Applied::Dispute));
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(-3_0000), Amount(5_0000), Amount(2_0000))
+    );
+}
+
+#[test]
+fn transaction_policy_withdrawal_to_held() {
+    use transaction::*;
+
+    let mut executor = Executor::default().with_dispute_policy(WithdrawalToHeld);
+    dispute_withdrawl(&mut executor, &[InputType::Dispute]);
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(2_0000), Amount(3_0000), Amount(5_0000))
+    );
+
+    let mut executor = Executor::default().with_dispute_policy(WithdrawalToHeld);
+    dispute_withdrawl(&mut executor, &[InputType::Dispute, InputType::Resolve]);
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(2_0000), Amount(0), Amount(2_0000))
+    );
+
+    let mut executor = Executor::default().with_dispute_policy(WithdrawalToHeld);
+    dispute_withdrawl(&mut executor, &[InputType::Dispute, InputType::Chargeback]);
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(5_0000), Amount(0), Amount(5_0000))
+    );
+    assert!(out[0].locked);
+}
+
+#[cfg(test)]
+fn dispute_overdrawn_deposit(
+    policy: transaction::NegativeBalancePolicy,
+    last: Option<transaction::InputType>,
+) -> (
+    transaction::Executor,
+    Vec<Result<transaction::Applied, transaction::Rejection>>,
+) {
+    use transaction::*;
+
+    let input = |ty, tx, amount| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let mut executor = Executor::default().with_negative_balance_policy(policy);
+    let mut inputs = vec![
+        input(InputType::Deposit, 1, Some(Amount(5_0000))),
+        input(InputType::Withdrawl, 2, Some(Amount(3_0000))),
+        input(InputType::Dispute, 1, None),
+    ];
+    inputs.extend(last.map(|ty| input(ty, 1, None)));
+    let outcomes = inputs.into_iter().map(|i| executor.process(i)).collect();
+    (executor, outcomes)
+}
+
+#[test]
+fn transaction_negative_balance_reject() {
+    use transaction::*;
+
+    let (mut executor, outcomes) = dispute_overdrawn_deposit(NegativeBalancePolicy::Reject, None);
+    assert_eq!(outcomes.last(), Some(&Err(Rejection::InsufficientFunds)));
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(2_0000), Amount(0), Amount(2_0000))
+    );
+    assert!(executor.exposures().is_empty());
+
+    let (executor, _) = dispute_overdrawn_deposit(NegativeBalancePolicy::Allow, None);
+    let exposures = executor.exposures();
+    assert_eq!(exposures.len(), 1);
+    assert_eq!(exposures[0].available, Amount(-3_0000));
+    assert!(!exposures[0].flagged);
+}
+
+#[test]
+fn transaction_negative_balance_partial_hold() {
+    use transaction::*;
+
+    let (mut executor, outcomes) =
+        dispute_overdrawn_deposit(NegativeBalancePolicy::PartialHold, None);
+    assert_eq!(outcomes.last(), Some(&Ok(Applied::Dispute)));
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(0), Amount(2_0000), Amount(2_0000))
+    );
+    let exposures = executor.exposures();
+    assert_eq!(exposures.len(), 1);
+    assert_eq!(exposures[0].shortfall, Amount(3_0000));
+    assert!(exposures[0].flagged);
+
+    //resolve releases only what was held
+    let (mut executor, _) =
+        dispute_overdrawn_deposit(NegativeBalancePolicy::PartialHold, Some(InputType::Resolve));
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(2_0000), Amount(0), Amount(2_0000))
+    );
+    assert_eq!(executor.exposures()[0].shortfall, Amount(0));
+}
+
+#[test]
+fn transaction_negative_balance_partial_hold_chargeback() {
+    use transaction::*;
+
+    //chargeback takes what was held and the shortfall from available
+    let (mut executor, outcomes) = dispute_overdrawn_deposit(
+        NegativeBalancePolicy::PartialHold,
+        Some(InputType::Chargeback),
+    );
+    assert_eq!(outcomes.last(), Some(&Ok(Applied::Chargeback)));
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(
+        (out[0].available, out[0].held, out[0].total),
+        (Amount(-3_0000), Amount(0), Amount(-3_0000))
+    );
+    assert!(out[0].locked);
+    let exposures = executor.exposures();
+    assert_eq!(exposures[0].available, Amount(-3_0000));
+    assert_eq!(exposures[0].shortfall, Amount(0));
+    assert!(exposures[0].flagged);
+}
+
+#[test]
+fn transaction_lock_unlock() {
+    use transaction::*;
+
+    let mut executor = Executor::default();
+    let input = |ty, tx, amount| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let inputs = [
+        input(InputType::Deposit, 1, Some(Amount(5_0000))),
+        input(InputType::Dispute, 1, None),
+        input(InputType::Chargeback, 1, None),
+        input(InputType::Deposit, 2, Some(Amount(1_0000))),
+        input(InputType::Lock, 100, None),
+        input(InputType::Unlock, 101, Some(Amount(1_0000))),
+        input(InputType::Unlock, 101, None),
+        input(InputType::Unlock, 102, None),
+        input(InputType::Deposit, 3, Some(Amount(2_0000))),
+        input(InputType::Lock, 103, None),
+    ];
+    let outcomes: Vec<_> = inputs.into_iter().map(|i| executor.process(i)).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            Ok(Applied::Deposit),
+            Ok(Applied::Dispute),
+            Ok(Applied::Chargeback),
+            Err(Rejection::AccountLocked),
+            Err(Rejection::AlreadyLocked),
+            Err(Rejection::UnexpectedAmount),
+            Ok(Applied::Unlock),
+            Err(Rejection::NotLocked),
+            Ok(Applied::Deposit),
+            Ok(Applied::Lock),
+        ]
+    );
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(out[0].total, Amount(2_0000));
+    assert!(out[0].locked);
+
+    //with the position of the input that made the change
+    let actions: Vec<_> = executor
+        .audit()
+        .iter()
+        .map(|x| (x.tx, x.action, x.position))
+        .collect();
+    assert_eq!(
+        actions,
+        vec![
+            (Tx(1), AuditAction::ChargebackLock, 3),
+            (Tx(101), AuditAction::AdminUnlock, 7),
+            (Tx(103), AuditAction::AdminLock, 10),
+        ]
+    );
+}
+
+#[test]
+fn transaction_admin_types_from_csv() {
+    use transaction::*;
+
+    let data = "type,client,tx,amount\nLOCK,4,7,\nunlock, 4, 8,\n";
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+    let inputs: Vec<Input> = reader.deserialize().map(|x| x.unwrap()).collect();
+    assert_eq!(inputs[0].ty, InputType::Lock);
+    assert_eq!(inputs[1].ty, InputType::Unlock);
+
+    let mut executor = Executor::default();
+    for (row, i) in (2..).zip(inputs) {
+        executor.process_at(row, i).unwrap();
+    }
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for i in executor.audit() {
+        writer.serialize(i).unwrap();
+    }
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "client,tx,action,position\n4,7,admin_lock,2\n4,8,admin_unlock,3\n"
+    );
+}
+
+#[test]
+fn transaction_tx_history() {
+    use transaction::*;
+
+    let input = |ty, tx, amount| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let inputs = [
+        input(InputType::Deposit, 1, Some(Amount(5_0000))),
+        input(InputType::Dispute, 1, None),
+        input(InputType::Resolve, 1, None),
+        input(InputType::Dispute, 2, None),
+        input(InputType::Dispute, 1, None),
+        input(InputType::Dispute, 1, None),
+        input(InputType::Chargeback, 1, None),
+    ];
+
+    let mut lean = Executor::default();
+    let mut executor = Executor::default().with_tx_history();
+    for (i, x) in inputs.into_iter().enumerate() {
+        let _ = lean.process(x);
+        let _ = executor.process_at(10 + i as u64, x);
+    }
+    assert_eq!(lean.tx_history(Client(1), Tx(1)), None);
+    assert_eq!(executor.tx_history(Client(1), Tx(2)), None);
+
+    let transitions: Vec<_> = executor
+        .tx_history(Client(1), Tx(1))
+        .unwrap()
+        .iter()
+        .map(|x| (x.status, x.position))
+        .collect();
+    assert_eq!(
+        transitions,
+        vec![
+            (DisputeStatus::Eligible, 10),
+            (DisputeStatus::Pending, 11),
+            (DisputeStatus::Eligible, 12),
+            (DisputeStatus::Pending, 14),
+            (DisputeStatus::Complete, 16),
+        ]
+    );
+    assert_eq!(executor.position(), 16);
+}
+
+#[test]
+fn transaction_dispute_limits() {
+    use transaction::*;
+
+    let input = |ty, tx, amount| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let limits = DisputeLimits {
+        max_disputes: Some(2),
+        window: Some(DisputeWindow::Inputs(5)),
+    };
+    let mut executor = Executor::default().with_dispute_limits(limits);
+    let inputs = [
+        input(InputType::Deposit, 1, Some(Amount(5_0000))),
+        input(InputType::Dispute, 1, None),
+        input(InputType::Resolve, 1, None),
+        input(InputType::Dispute, 1, None),
+        input(InputType::Resolve, 1, None),
+        input(InputType::Dispute, 1, None),
+        input(InputType::Deposit, 2, Some(Amount(1_0000))),
+        input(InputType::Dispute, 2, None),
+        input(InputType::Resolve, 2, None),
+    ];
+    let outcomes: Vec<_> = inputs.into_iter().map(|i| executor.process(i)).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            Ok(Applied::Deposit),
+            Ok(Applied::Dispute),
+            Ok(Applied::Resolve),
+            Ok(Applied::Dispute),
+            Ok(Applied::Resolve),
+            Err(Rejection::TooManyDisputes),
+            Ok(Applied::Deposit),
+            Ok(Applied::Dispute),
+            Ok(Applied::Resolve),
+        ]
+    );
+
+    //tx 2 was applied at position 7, 5 inputs later is the last chance
+    assert_eq!(
+        executor.process_at(13, input(InputType::Dispute, 2, None)),
+        Err(Rejection::WindowClosed)
+    );
+    assert_eq!(
+        executor.process_at(12, input(InputType::Dispute, 2, None)),
+        Ok(Applied::Dispute)
+    );
+}
+
+#[test]
+fn transaction_dispute_window_secs() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount,timestamp
+deposit,1,1,5.0,1000
+deposit,1,2,5.0,1000
+deposit,1,3,5.0
+dispute,1,1,,1060
+dispute,1,2,,1061
+dispute,1,3,,5000
+deposit,2,4,1.0,2000
+dispute,2,4,,
+";
+    let limits = DisputeLimits {
+        max_disputes: None,
+        window: Some(DisputeWindow::Seconds(60)),
+    };
+    let mut executor = Executor::default().with_dispute_limits(limits);
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let outcomes: Vec<_> = reader
+        .map(|x| {
+            let (row, input) = x.unwrap();
+            executor.process_at(row, input)
+        })
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            Ok(Applied::Deposit),
+            Ok(Applied::Deposit),
+            Ok(Applied::Deposit),
+            Ok(Applied::Dispute),
+            Err(Rejection::WindowClosed),
+            Ok(Applied::Dispute), //tx 3 has no timestamp to measure from
+            Ok(Applied::Deposit),
+            Err(Rejection::WindowClosed),
+        ]
+    );
+}
+
+#[test]
+fn transaction_partial_dispute() {
+    use transaction::*;
+
+    let input = |ty, tx, amount| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp: None,
+        currency: None,
+        to: None,
+    };
+    let mut executor = Executor::default();
+    let inputs = [
+        input(InputType::Deposit, 1, Some(Amount(10_0000))),
+        input(InputType::Deposit, 2, Some(Amount(4_0000))),
+        input(InputType::Dispute, 1, Some(Amount(3_0000))),
+        input(InputType::Resolve, 1, None),
+        input(InputType::Dispute, 1, Some(Amount(2_5000))),
+        input(InputType::Dispute, 2, None),
+    ];
+    for i in inputs {
+        executor.process(i).unwrap();
+    }
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(out[0].available, Amount(7_5000));
+    assert_eq!(out[0].held, Amount(6_5000));
+    assert_eq!(out[0].total, Amount(14_0000));
+    assert_eq!(
+        executor.tx_disputed(Client(1), Tx(1)),
+        Some(DisputedAmounts {
+            held: Amount(2_5000),
+            charged_back: Amount::ZERO,
+        })
+    );
+
+    //chargeback reverses only the disputed part
+    executor
+        .process(input(InputType::Chargeback, 1, None))
+        .unwrap();
+    let out: Vec<_> = executor.output().collect();
+    assert_eq!(out[0].available, Amount(7_5000));
+    assert_eq!(out[0].held, Amount(4_0000));
+    assert_eq!(out[0].total, Amount(11_5000));
+    assert!(out[0].locked);
+    assert_eq!(
+        executor.tx_disputed(Client(1), Tx(1)),
+        Some(DisputedAmounts {
+            held: Amount::ZERO,
+            charged_back: Amount(2_5000),
+        })
+    );
+    assert_eq!(executor.tx_disputed(Client(1), Tx(3)), None);
+}
+
+#[test]
+fn transaction_currencies() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount,currency
+deposit,1,1,10.0,usd
+deposit,1,2,5.0,EUR
+deposit,1,3,2.0,
+withdrawal,1,4,6.0,EUR
+withdrawal,1,5,6.0,USD
+dispute,1,2,,USD
+dispute,1,2,,EUR
+chargeback,1,2,,
+deposit,1,6,1.0,USD
+";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let mut executor = Executor::default();
+    let outcomes: Vec<_> = reader.map(|x| executor.process(x.unwrap().1)).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            Ok(Applied::Deposit),
+            Ok(Applied::Deposit),
+            Ok(Applied::Deposit),
+            Err(Rejection::InsufficientFunds),
+            Ok(Applied::Withdrawl),
+            Err(Rejection::CurrencyMismatch),
+            Ok(Applied::Dispute),
+            Ok(Applied::Chargeback),
+            Err(Rejection::AccountLocked),
+        ]
+    );
+
+    let mut out: Vec<_> = executor.output().collect();
+    out.sort_by_key(|x| x.currency);
+    let usd: Currency = "USD".parse().unwrap();
+    let eur: Currency = "EUR".parse().unwrap();
+    let rows: Vec<_> = out
+        .iter()
+        .map(|x| (x.currency, x.total, x.locked))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (None, Amount(2_0000), true),
+            (Some(eur), Amount(0), true),
+            (Some(usd), Amount(4_0000), true),
+        ]
+    );
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.serialize(&out[2]).unwrap();
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "client,currency,available,held,total,locked\n1,USD,4.0000,0.0000,4.0000,true\n"
+    );
+    assert!("EURO2026".parse::<Currency>().is_ok());
+    assert!("EURO-2026".parse::<Currency>().is_err());
+
+    //codes as stored are only taken in the form parsing gives them
+    let euro: Currency = "euro2026".parse().unwrap();
+    assert_eq!(euro.as_bytes(), b"EURO2026");
+    assert_eq!(Currency::try_from(*b"USD\0\0\0\0\0"), Ok(usd));
+    assert!(Currency::try_from(*b"usd\0\0\0\0\0").is_err());
+    assert!(Currency::try_from(*b"U\0SD\0\0\0\0").is_err());
+    assert!(Currency::try_from([0xff; 8]).is_err());
+    assert!(Currency::try_from([0; 8]).is_err());
+}
+
+#[test]
+fn transaction_currency_of_reused_tx() {
+    use transaction::*;
+
+    //tx ids are unique per client, both deposits of tx 5 are kept with their currency
+    let data = "\
+type,client,tx,amount,currency
+deposit,1,5,10.0,USD
+deposit,2,5,4.0,
+dispute,2,5,,
+dispute,1,5,,USD
+dispute,2,6,,
+";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let mut executor = Executor::default();
+    let outcomes: Vec<_> = reader.map(|x| executor.process(x.unwrap().1)).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            Ok(Applied::Deposit),
+            Ok(Applied::Deposit),
+            Ok(Applied::Dispute),
+            Ok(Applied::Dispute),
+            Err(Rejection::UnknownTx),
+        ]
+    );
+    let mut out: Vec<_> = executor.output().collect();
+    out.sort_by_key(|x| x.client.0);
+    let rows: Vec<_> = out
+        .iter()
+        .map(|x| (x.client, x.currency, x.available, x.held))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (
+                Client(1),
+                Some("USD".parse().unwrap()),
+                Amount(0),
+                Amount(10_0000)
+            ),
+            (Client(2), None, Amount(0), Amount(4_0000)),
+        ]
+    );
+}
+
+#[test]
+fn transaction_locks_add_no_balances() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount,currency
+deposit,1,1,10.0,USD
+dispute,1,1,,USD
+chargeback,1,1,,USD
+deposit,1,2,5.0,EUR
+lock,2,100,,
+";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let mut executor = Executor::default();
+    let outcomes: Vec<_> = reader.map(|x| executor.process(x.unwrap().1)).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            Ok(Applied::Deposit),
+            Ok(Applied::Dispute),
+            Ok(Applied::Chargeback),
+            Err(Rejection::AccountLocked),
+            Ok(Applied::Lock),
+        ]
+    );
+
+    //only the usd balance of client 1, and a row for client 2 that has only a lock
+    let mut out: Vec<_> = executor.output().collect();
+    out.sort_by_key(|x| x.client.0);
+    let rows: Vec<_> = out
+        .iter()
+        .map(|x| (x.client, x.currency, x.total, x.locked))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (Client(1), Some("USD".parse().unwrap()), Amount(0), true),
+            (Client(2), None, Amount(0), true),
+        ]
+    );
+
+    let mut bytes = vec![];
+    executor.snapshot(&mut bytes).unwrap();
+    let mut restored = Executor::restore(&bytes[..]).unwrap();
+    assert_eq!(restored, executor);
+    let unlock = "type,client,tx,amount\nunlock,2,101,\n";
+    for x in InputReader::from_reader(unlock.as_bytes(), IngestMode::Strict).unwrap() {
+        restored.process(x.unwrap().1).unwrap();
+    }
+    assert_eq!(restored.output().count(), 1);
+}
+
+#[test]
+fn transaction_transfer() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount,to
+deposit,1,1,10.0,
+transfer,1,2,15.0,2
+transfer,1,3,4.0,2
+transfer,2,4,1.0,2
+transfer,2,5,1.0,
+transfer,1,3,1.0,2
+lock,2,100,,
+transfer,1,6,1.0,2
+transfer,2,7,1.0,1
+unlock,2,101,,
+transfer,2,8,1.5,3
+dispute,1,3,,
+";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let mut executor = Executor::default();
+    let outcomes: Vec<_> = reader.map(|x| executor.process(x.unwrap().1)).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            Ok(Applied::Deposit),
+            Err(Rejection::InsufficientFunds),
+            Ok(Applied::Transfer),
+            Err(Rejection::SelfTransfer),
+            Err(Rejection::MissingDestination),
+            Err(Rejection::DuplicateTx),
+            Ok(Applied::Lock),
+            Err(Rejection::AccountLocked),
+            Err(Rejection::AccountLocked),
+            Ok(Applied::Unlock),
+            Ok(Applied::Transfer),
+            Err(Rejection::UnknownTx),
+        ]
+    );
+
+    //a rejected transfer credits nothing
+    let mut out: Vec<_> = executor.output().collect();
+    out.sort_by_key(|x| x.client.0);
+    let rows: Vec<_> = out
+        .iter()
+        .map(|x| (x.client, x.available, x.total))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (Client(1), Amount(6_0000), Amount(6_0000)),
+            (Client(2), Amount(2_5000), Amount(2_5000)),
+            (Client(3), Amount(1_5000), Amount(1_5000)),
+        ]
+    );
+}
+
+#[test]
+fn transaction_rejected_inputs_keep_no_tx() {
+    use transaction::*;
+
+    let data = "\
+type,client,tx,amount,currency
+deposit,1,1,10.0,USD
+withdrawal,1,2,20.0,USD
+withdrawal,1,3,1.0,EUR
+lock,1,100,,
+deposit,1,4,5.0,
+unlock,1,101,,
+deposit,1,4,5.0,
+withdrawal,1,2,2.0,USD
+";
+    let reader = InputReader::from_reader(data.as_bytes(), IngestMode::Strict).unwrap();
+    let mut executor = Executor::default();
+    let outcomes: Vec<_> = reader.map(|x| executor.process(x.unwrap().1)).collect();
+    //tx 4 and tx 2 were rejected before, so they are new when applied
+    assert_eq!(
+        outcomes,
+        vec![
+            Ok(Applied::Deposit),
+            Err(Rejection::InsufficientFunds),
+            Err(Rejection::InsufficientFunds),
+            Ok(Applied::Lock),
+            Err(Rejection::AccountLocked),
+            Ok(Applied::Unlock),
+            Ok(Applied::Deposit),
+            Ok(Applied::Withdrawl),
+        ]
+    );
+
+    //no eur row for the withdrawl that could not be covered
+    let mut out: Vec<_> = executor.output().collect();
+    out.sort_by_key(|x| x.currency);
+    let rows: Vec<_> = out.iter().map(|x| (x.currency, x.total)).collect();
+    assert_eq!(
+        rows,
+        vec![
+            (None, Amount(5_0000)),
+            (Some("USD".parse().unwrap()), Amount(8_0000)),
+        ]
+    );
+}
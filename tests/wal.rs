@@ -0,0 +1,215 @@
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+use common::InputGen;
+
+#[cfg(test)]
+fn history(n: usize) -> Vec<transaction::Input> {
+    //some amounts are not positive, those inputs are not logged
+    InputGen::new(3).with_amounts(-1_0000..50_0000).inputs(n)
+}
+
+#[test]
+fn wal_replay_and_torn_tail() {
+    use transaction::*;
+
+    let inputs = history(500);
+    let mut executor = Executor::default();
+    let mut wal = Wal::new(vec![], 0).unwrap();
+    let mut outcomes = vec![];
+    for (row, i) in (2..).zip(inputs.iter()) {
+        outcomes.push(executor.process_logged(row, *i, &mut wal).unwrap());
+    }
+    wal.flush().unwrap();
+    let logged = outcomes
+        .iter()
+        .filter(|x| !matches!(x, Err(Rejection::NonPositiveAmount)))
+        .count() as u64;
+    assert_eq!(wal.seq(), logged);
+    assert_eq!(executor.wal_seq(), logged);
+
+    let path = std::env::temp_dir().join(format!("wal_{}.log", std::process::id()));
+    let log = wal.into_inner();
+    std::fs::write(&path, &log).unwrap();
+
+    let mut recovered = Executor::default();
+    let replayed = recovered.replay(&log[..]).unwrap();
+    assert_eq!(replayed.applied, logged);
+    assert_eq!(replayed.last_position, Some(501));
+    assert!(!replayed.torn);
+    assert_eq!(recovered, executor);
+
+    //cut the last entry short and damage the one before
+    let entry_len = 39;
+    let mut torn = log[..log.len() - 5].to_vec();
+    let before_last = torn.len() - (entry_len - 5) - entry_len;
+    torn[before_last + 20] ^= 0xff;
+    std::fs::write(&path, &torn).unwrap();
+
+    let mut recovered = Executor::default();
+    let replayed = recovered
+        .replay(std::fs::File::open(&path).unwrap())
+        .unwrap();
+    assert_eq!(replayed.applied, logged - 2);
+    assert!(replayed.torn);
+
+    //opening cuts the damage off and numbering continues after the valid entries
+    let mut wal = Wal::open(&path, 0).unwrap();
+    assert_eq!(wal.seq(), logged - 2);
+    wal.sync().unwrap();
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().len() as usize,
+        before_last
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn wal_after_snapshot() {
+    use transaction::*;
+
+    let inputs = history(400);
+    let (first, second) = inputs.split_at(200);
+
+    let mut executor = Executor::default();
+    let mut wal = Wal::new(vec![], 0).unwrap();
+    for (row, i) in (2..).zip(first.iter()) {
+        let _ = executor.process_logged(row, *i, &mut wal).unwrap();
+    }
+    let mut snapshot = vec![];
+    executor.snapshot(&mut snapshot).unwrap();
+    for (row, i) in (202..).zip(second.iter()) {
+        let _ = executor.process_logged(row, *i, &mut wal).unwrap();
+    }
+    let log = wal.into_inner();
+
+    //entries the snapshot already covers are skipped
+    let mut recovered = Executor::restore(&snapshot[..]).unwrap();
+    let replayed = recovered.replay(&log[..]).unwrap();
+    assert_eq!(replayed.skipped + replayed.applied, executor.wal_seq());
+    assert_eq!(recovered, executor);
+
+    let mut again = Executor::restore(&snapshot[..]).unwrap();
+    again.replay(&log[..]).unwrap();
+    let replayed = again.replay(&log[..]).unwrap();
+    assert_eq!(replayed.applied, 0);
+    assert_eq!(again, executor);
+}
+
+#[test]
+fn wal_keeps_timestamps_and_currencies() {
+    use transaction::*;
+
+    let limits = DisputeLimits {
+        max_disputes: None,
+        window: Some(DisputeWindow::Seconds(10)),
+    };
+    let input = |ty, tx, amount, timestamp| Input {
+        ty,
+        client: Client(1),
+        tx: Tx(tx),
+        amount,
+        timestamp,
+        currency: None,
+        to: None,
+    };
+    let inputs = [
+        input(InputType::Deposit, 1, Some(Amount(1_0000)), Some(100)),
+        input(InputType::Deposit, 2, Some(Amount(1_0000)), None),
+        input(InputType::Dispute, 1, None, Some(111)),
+        input(InputType::Dispute, 2, None, Some(500)),
+        Input {
+            currency: Some("EUR".parse().unwrap()),
+            ..input(InputType::Deposit, 3, Some(Amount(1_0000)), Some(600))
+        },
+        Input {
+            currency: Some("EUR".parse().unwrap()),
+            ..input(InputType::Deposit, 4, Some(Amount(1_0000)), None)
+        },
+        Input {
+            to: Some(Client(2)),
+            ..input(InputType::Transfer, 5, Some(Amount(5000)), None)
+        },
+        Input {
+            currency: Some("EUR".parse().unwrap()),
+            to: Some(Client(2)),
+            ..input(InputType::Transfer, 6, Some(Amount(5000)), Some(700))
+        },
+    ];
+    let mut executor = Executor::default().with_dispute_limits(limits);
+    let mut wal = Wal::new(vec![], 0).unwrap();
+    for (row, i) in (2..).zip(inputs) {
+        let _ = executor.process_logged(row, i, &mut wal).unwrap();
+    }
+    let log = wal.into_inner();
+    let entries: Vec<_> = WalReader::new(&log[..])
+        .unwrap()
+        .map(|x| {
+            let x = x.unwrap();
+            (x.timestamp, x.currency.is_some())
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (Some(100), false),
+            (None, false),
+            (Some(111), false),
+            (Some(500), false),
+            (Some(600), true),
+            (None, true),
+            (None, false),
+            (Some(700), true),
+        ]
+    );
+
+    let mut recovered = Executor::default().with_dispute_limits(limits);
+    recovered.replay(&log[..]).unwrap();
+    assert_eq!(recovered, executor);
+    let mut snapshot = vec![];
+    recovered.snapshot(&mut snapshot).unwrap();
+    assert_eq!(Executor::restore(&snapshot[..]).unwrap(), executor);
+}
+
+#[test]
+fn wal_marks_finished_runs() {
+    use transaction::*;
+
+    let inputs = history(100);
+    let path = std::env::temp_dir().join(format!("wal_finished_{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut executor = Executor::default();
+    let mut wal = Wal::open(&path, 0).unwrap();
+    for (row, i) in (2..).zip(inputs[..50].iter()) {
+        let _ = executor.process_logged(row, *i, &mut wal).unwrap();
+    }
+    let seq = wal.finish_run().unwrap();
+    wal.sync().unwrap();
+    drop(wal);
+
+    let mut recovered = Executor::default();
+    let replayed = recovered
+        .replay(std::fs::File::open(&path).unwrap())
+        .unwrap();
+    assert!(replayed.finished);
+    assert_eq!(replayed.applied, executor.wal_seq());
+    assert_eq!(recovered, executor);
+
+    //the next run numbers its entries after the marker and is unfinished until it ends
+    let mut wal = Wal::open(&path, recovered.wal_seq()).unwrap();
+    assert_eq!(wal.seq(), seq);
+    for (row, i) in (2..).zip(inputs[50..].iter()) {
+        let _ = executor.process_logged(row, *i, &mut wal).unwrap();
+    }
+    wal.sync().unwrap();
+    drop(wal);
+    let mut recovered = Executor::default();
+    let replayed = recovered
+        .replay(std::fs::File::open(&path).unwrap())
+        .unwrap();
+    assert!(!replayed.finished);
+    assert_eq!(replayed.last_position, Some(51));
+    assert_eq!(recovered, executor);
+    std::fs::remove_file(path).unwrap();
+}